@@ -1,27 +1,136 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::hook::HookShell;
+use crate::meta::ProfileSort;
+use crate::model::{ProjectTarget, SwitchMode};
+use crate::output::OutputFormat;
+use crate::shell::Shell;
 
 #[derive(Parser)]
 #[command(name = "ccm", version, about = "Manage multiple Claude Code configurations (profiles) and switch/launch", long_about = None)]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Option<Commands>,
+    /// Print results (and errors) as json or yaml instead of text; messages then go to stderr. Not supported by ui, hook and hook-env
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+}
+
+/// Input for `ccm add`; any of these besides --env skips the full questionnaire
+#[derive(Args, Debug, Default)]
+pub struct AddArgs {
+    /// Additional environment variables (can be used multiple times: --env KEY=VALUE)
+    #[arg(long)]
+    pub env: Vec<String>,
+    /// Start from a provider preset (see `ccm presets`), so only the token is asked for
+    #[arg(long, conflicts_with = "from_json")]
+    pub preset: Option<String>,
+    /// ANTHROPIC_BASE_URL
+    #[arg(long)]
+    pub base_url: Option<String>,
+    /// Read ANTHROPIC_AUTH_TOKEN from the first line of stdin
+    #[arg(long, conflicts_with = "token_env")]
+    pub token_stdin: bool,
+    /// Read ANTHROPIC_AUTH_TOKEN from this environment variable
+    #[arg(long, value_name = "VAR")]
+    pub token_env: Option<String>,
+    /// ANTHROPIC_MODEL
+    #[arg(long)]
+    pub model: Option<String>,
+    /// ANTHROPIC_SMALL_FAST_MODEL
+    #[arg(long)]
+    pub small_fast_model: Option<String>,
+    /// API_TIMEOUT_MS
+    #[arg(long)]
+    pub timeout_ms: Option<u64>,
+    /// Start from a profile JSON file, or `-` for stdin; other flags override its values
+    #[arg(long, value_name = "FILE")]
+    pub from_json: Option<String>,
+    /// Never prompt; fail if ANTHROPIC_BASE_URL or a token is missing
+    #[arg(long)]
+    pub no_input: bool,
+    /// Overwrite the profile if it already exists
+    #[arg(long)]
+    pub force: bool,
+    /// Save the profile even if `ccm validate` would report errors
+    #[arg(long)]
+    pub no_validate: bool,
+}
+
+/// Options for `ccm ls`
+#[derive(Args, Debug, Default)]
+pub struct ListArgs {
+    /// Also show each profile's description, tags, provider and usage
+    #[arg(short, long)]
+    pub long: bool,
+    /// Only list profiles with this tag (can be used multiple times; all must match)
+    #[arg(long)]
+    pub tag: Vec<String>,
+    /// Order of the list
+    #[arg(long, value_enum, default_value_t = ProfileSort::Name)]
+    pub sort: ProfileSort,
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
-    /// Add a profile with interactive prompts for ANTHROPIC_BASE_URL, ANTHROPIC_AUTH_TOKEN, ANTHROPIC_MODEL, API_TIMEOUT_MS, ANTHROPIC_SMALL_FAST_MODEL, and CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC. Use --env for additional environment variables.
+    /// Add a profile. Without input flags, prompts for ANTHROPIC_BASE_URL, ANTHROPIC_AUTH_TOKEN, ANTHROPIC_MODEL, API_TIMEOUT_MS, ANTHROPIC_SMALL_FAST_MODEL, and CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC. Use --env for additional environment variables.
     Add {
         /// Profile name
         name: String,
-        /// Additional environment variables (can be used multiple times: --env KEY=VALUE)
-        #[arg(long)]
-        env: Vec<String>,
+        #[command(flatten)]
+        args: AddArgs,
     },
     /// List saved profiles (shows current active profile)
     #[command(visible_alias = "ls")]
-    List,
+    List {
+        #[command(flatten)]
+        args: ListArgs,
+    },
+    /// Show or set a profile's description, tags and provider (kept apart from its settings)
+    Describe {
+        name: String,
+        /// New description; an empty string clears it
+        description: Option<String>,
+        /// Add a tag (can be used multiple times)
+        #[arg(long)]
+        tag: Vec<String>,
+        /// Remove a tag (can be used multiple times)
+        #[arg(long)]
+        untag: Vec<String>,
+        /// Provider name, e.g. deepseek; an empty string clears it
+        #[arg(long)]
+        provider: Option<String>,
+    },
+    /// List provider presets for `ccm add --preset`
+    Presets,
     /// Show profile content
-    Show { name: String },
+    Show {
+        name: String,
+        /// Print the profile with its `extends` chain flattened
+        #[arg(long)]
+        resolved: bool,
+        /// Print tokens, keys, passwords and other credentials unmasked
+        #[arg(long)]
+        reveal: bool,
+    },
+    /// Check profiles for unknown keys and invalid values (all profiles if no name is given)
+    Validate { name: Option<String> },
+    /// Explain what Claude Code will use in the current directory, and where each value comes from
+    Status,
+    /// Show how a profile differs from another profile, or from the settings it is applied to
+    Diff {
+        name: String,
+        /// Profile to compare against
+        other: Option<String>,
+        /// Compare against ~/.claude/settings.json (the default)
+        #[arg(long, conflicts_with_all = ["other", "project"])]
+        settings: bool,
+        /// Compare against the current project's .claude/settings.json with
+        /// settings.local.json merged over it
+        #[arg(long, conflicts_with = "other")]
+        project: bool,
+    },
     /// Remove a profile
     #[command(visible_alias = "rm")]
     Remove { name: String },
@@ -29,12 +138,30 @@ pub enum Commands {
     #[command(visible_alias = "swc")]
     Switch {
         name: String,
-        /// Apply to current project instead of global (uses current working directory)
+        /// Apply to current project instead of global (the nearest mapped, `.claude/` or git root directory)
         #[arg(short, long)]
         project: bool,
+        /// How to write the global settings, overriding the profile's `switchMode`
+        #[arg(long, value_enum, conflicts_with = "project")]
+        mode: Option<SwitchMode>,
+        /// Project settings file to write: `local` (.claude/settings.local.json) or
+        /// `shared` (.claude/settings.json, with secrets kept in the local file)
+        #[arg(long, value_enum, requires = "project")]
+        target: Option<ProjectTarget>,
+        /// Switch even if `ccm validate` would report errors
+        #[arg(long)]
+        no_validate: bool,
     },
-    /// Run Claude Code with the current profile (use 'switch' first to select a profile)
-    Run,
+    /// Run Claude Code with the current profile, or with a profile's env injected only into the claude process
+    Run {
+        /// Profile to run with, without switching (defaults to the current profile)
+        profile: Option<String>,
+        /// Arguments passed through to claude (after --)
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+    /// Check that a profile's endpoint, token and models work
+    Test { name: String },
     /// Import current Claude settings as a new profile
     Import { name: String },
     /// Rename a profile from original name to new name
@@ -45,14 +172,23 @@ pub enum Commands {
         new: String,
     },
     /// Edit a profile using the default editor (opens profile JSON file in editor)
-    Edit { name: String },
+    Edit {
+        name: String,
+        /// Keep the edits even if `ccm validate` would report errors
+        #[arg(long)]
+        no_validate: bool,
+    },
     /// Launch terminal UI mode (interactive profile management)
     #[command(visible_alias = "tui")]
     Ui,
     /// Test TUI components without requiring a terminal
     TestTui,
     /// Sync current profile with current Claude settings
-    Sync,
+    Sync {
+        /// Sync the current project's profile with its .claude settings instead
+        #[arg(short, long)]
+        project: bool,
+    },
     /// Check for updates and install the latest version from GitHub
     Update {
         /// Only check for updates without installing
@@ -61,4 +197,81 @@ pub enum Commands {
     },
     /// Clear project-specific profile setting (revert to global)
     ClearProject,
+    /// Print a profile's env as shell exports, e.g. eval "$(ccm env deepseek)"
+    Env {
+        /// Profile name
+        name: String,
+        /// Output syntax (defaults to the shell in $SHELL)
+        #[arg(long, value_enum)]
+        shell: Option<Shell>,
+        /// Print commands that remove the profile's variables instead
+        #[arg(long)]
+        unset: bool,
+    },
+    /// Print a shell hook that applies project profiles on cd, e.g. eval "$(ccm hook bash)"
+    Hook {
+        /// Shell to generate the hook for
+        #[arg(value_enum)]
+        shell: HookShell,
+    },
+    /// Print env changes for the current directory (used by the shell hook)
+    #[command(hide = true)]
+    HookEnv {
+        #[arg(long, value_enum)]
+        shell: HookShell,
+    },
+    /// List, prune and clear project profile mappings
+    Projects {
+        #[command(subcommand)]
+        action: ProjectsCommands,
+    },
+    /// Manage secrets in the encrypted vault (reference them in profiles as "vault:<name>")
+    Secret {
+        #[command(subcommand)]
+        action: SecretCommands,
+    },
+}
+
+impl Commands {
+    /// Whether the command can print its result as json or yaml.
+    /// The others draw a terminal UI or print shell code.
+    pub fn supports_structured_output(&self) -> bool {
+        !matches!(
+            self,
+            Self::Ui | Self::TestTui | Self::Hook { .. } | Self::HookEnv { .. }
+        )
+    }
+}
+
+#[derive(Subcommand)]
+pub enum ProjectsCommands {
+    /// List directories with a project profile and whether it is still applied
+    #[command(visible_alias = "ls")]
+    List,
+    /// Remove mappings whose directory or profile no longer exists
+    Prune,
+    /// Clear the project profile of a directory (like clear-project, for any path)
+    Clear { path: PathBuf },
+}
+
+#[derive(Subcommand)]
+pub enum SecretCommands {
+    /// Store a secret (prompts for the value with hidden input)
+    Set {
+        /// Secret name
+        name: String,
+        /// Read the value from stdin instead of prompting
+        #[arg(long)]
+        stdin: bool,
+    },
+    /// Print a secret's value
+    Get { name: String },
+    /// Remove a secret
+    #[command(visible_alias = "rm")]
+    Remove { name: String },
+    /// List secret names
+    #[command(visible_alias = "ls")]
+    List,
+    /// Move plaintext tokens from all profiles into the vault
+    Migrate,
 }
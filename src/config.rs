@@ -1,8 +1,16 @@
 use anyhow::{Context, Result};
+use clap::ValueEnum;
 use dirs::config_dir;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
 use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+use crate::model::ProjectTarget;
 
 /// Get the ccm base directory path (without creating it)
 /// Can be overridden with CCM_CONFIG_DIR environment variable for testing
@@ -56,40 +64,212 @@ pub fn current_profile_path() -> PathBuf {
     ccm_dir().join("current")
 }
 
+/// Get the path to the file recording how the current profile was applied
+pub fn current_switch_mode_path() -> PathBuf {
+    ccm_dir().join("current_mode")
+}
+
+/// Get the path of the snapshot of what a profile last wrote to settings.json
+pub fn applied_snapshot_path(name: &str) -> PathBuf {
+    ccm_dir().join("applied").join(format!("{}.json", name))
+}
+
+/// Get the path to the encrypted secret vault
+pub fn vault_path() -> PathBuf {
+    ccm_dir().join("vault.json")
+}
+
+/// Get the path to the user's provider presets file
+pub fn presets_path() -> PathBuf {
+    ccm_dir().join("presets.json")
+}
+
 /// Get the current working directory
 pub fn get_current_working_dir() -> Result<PathBuf> {
     env::current_dir().context("getting current working directory")
 }
 
-/// Hash a directory path to create a unique identifier for the project
-/// Uses a simple hash to avoid filesystem-unfriendly characters
-fn hash_path(path: &PathBuf) -> String {
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hash, Hasher};
+/// Find the project a directory belongs to by walking up its parents: the
+/// nearest directory with a project profile mapping, else the nearest one with
+/// a `.claude/` directory (other than the one holding the global settings),
+/// else the git root, else the directory itself
+pub fn resolve_project_root(dir: &Path) -> Result<PathBuf> {
+    if let Some((mapped, _)) = find_project_mapping(dir)? {
+        return Ok(mapped);
+    }
+
+    let dir = canonical_project_dir(dir);
+    let global_claude_dir = claude_settings_path().parent().map(canonical_project_dir);
+    let home_claude_dir =
+        env::var_os("HOME").map(|h| canonical_project_dir(&Path::new(&h).join(".claude")));
+    if let Some(root) = dir.ancestors().find(|a| {
+        let claude_dir = a.join(".claude");
+        claude_dir.is_dir()
+            && Some(&claude_dir) != global_claude_dir.as_ref()
+            && Some(&claude_dir) != home_claude_dir.as_ref()
+    }) {
+        return Ok(root.to_path_buf());
+    }
+    if let Some(root) = dir.ancestors().find(|a| a.join(".git").exists()) {
+        return Ok(root.to_path_buf());
+    }
+    Ok(dir)
+}
+
+/// Get the project root for the current working directory
+pub fn current_project_root() -> Result<PathBuf> {
+    resolve_project_root(&get_current_working_dir()?)
+}
+
+/// Name of the marker recording which hash the project mapping files are keyed by
+const PROJECT_HASH_MARKER: &str = ".hash-fnv1a64";
+
+/// Resolve symlinks and `..` so every spelling of a directory maps to the same
+/// project; falls back to the path as given if it no longer exists
+fn canonical_project_dir(dir: &Path) -> PathBuf {
+    fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf())
+}
+
+/// Hash a directory path to create a unique identifier for the project.
+/// FNV-1a (64-bit) over the path's UTF-8 bytes: unlike `DefaultHasher`, its
+/// output is fixed, so mapping file names survive toolchain upgrades.
+fn hash_path(path: &Path) -> String {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let hash = path
+        .to_string_lossy()
+        .bytes()
+        .fold(OFFSET_BASIS, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+        });
+    format!("{:016x}", hash)
+}
+
+/// Re-key mapping files written by older versions (which hashed with
+/// `DefaultHasher` and did not resolve symlinks) using the `path` they store
+fn migrate_project_mappings() -> Result<()> {
+    let dir = project_profiles_dir();
+    let marker = dir.join(PROJECT_HASH_MARKER);
+    if !dir.exists() || marker.exists() {
+        return Ok(());
+    }
+
+    // When several old files map to the same directory (e.g. through a
+    // symlink), the most recently written one wins, whatever the listing order
+    let mut migrated: BTreeMap<PathBuf, (SystemTime, PathBuf)> = BTreeMap::new();
+    for entry in fs::read_dir(&dir).with_context(|| format!("reading {}", dir.display()))? {
+        let file = entry?.path();
+        if file.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let content = fs::read_to_string(&file)
+            .with_context(|| format!("reading project profile from {}", file.display()))?;
+        let Ok(mut value) = serde_json::from_str::<serde_json::Value>(&content) else {
+            continue;
+        };
+        let Some(original) = value.get("path").and_then(|v| v.as_str()) else {
+            continue;
+        };
+
+        let project_dir = canonical_project_dir(Path::new(original));
+        let target = dir.join(format!("{}.json", hash_path(&project_dir)));
+        if target == file {
+            continue;
+        }
+        let modified = modified_time(&file);
+        let existing = match migrated.get(&target) {
+            Some((time, source)) => Some((*time, source.clone())),
+            None if target.exists() => Some((modified_time(&target), target.clone())),
+            None => None,
+        };
+        if let Some((existing_time, existing_source)) = existing {
+            let (kept, dropped) = if modified > existing_time {
+                (&file, &existing_source)
+            } else {
+                (&existing_source, &file)
+            };
+            eprintln!(
+                "⚠️  {} and {} both map {}; keeping the newer {}",
+                existing_source.display(),
+                file.display(),
+                project_dir.display(),
+                kept.display()
+            );
+            if dropped == &file {
+                fs::remove_file(&file).with_context(|| {
+                    format!("removing stale project profile {}", file.display())
+                })?;
+                continue;
+            }
+        }
+        value["path"] = serde_json::Value::String(project_dir.to_string_lossy().into_owned());
+        fs::write(&target, serde_json::to_string_pretty(&value)?)
+            .with_context(|| format!("writing project profile to {}", target.display()))?;
+        fs::remove_file(&file)
+            .with_context(|| format!("removing old project profile {}", file.display()))?;
+        migrated.insert(target, (modified, file));
+    }
+
+    fs::write(&marker, "")
+        .with_context(|| format!("writing migration marker {}", marker.display()))?;
+    Ok(())
+}
+
+/// When a file was last written; unknown times sort first
+fn modified_time(file: &Path) -> SystemTime {
+    fs::metadata(file)
+        .and_then(|m| m.modified())
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
 
-    let mut hasher = DefaultHasher::new();
-    path.hash(&mut hasher);
-    format!("{:016x}", hasher.finish())
+/// Migrate mapping files from older versions, once per run, before any is looked up
+fn migrate_project_mappings_once() {
+    static MIGRATED: OnceLock<()> = OnceLock::new();
+    MIGRATED.get_or_init(|| {
+        if let Err(e) = migrate_project_mappings() {
+            eprintln!("⚠️  Failed to migrate project profile mappings: {:#}", e);
+        }
+    });
 }
 
 /// Get the path to the project profile marker file for a specific directory
-/// The file name is based on a hash of the directory path
-pub fn project_profile_path(project_dir: &PathBuf) -> PathBuf {
-    let hash = hash_path(project_dir);
+/// The file name is based on a hash of the canonical directory path
+pub fn project_profile_path(project_dir: &Path) -> PathBuf {
+    migrate_project_mappings_once();
+    let hash = hash_path(&canonical_project_dir(project_dir));
     project_profiles_dir().join(format!("{}.json", hash))
 }
 
-/// Get the project profile info (profile name and original path) for a directory
-/// Returns (profile_name, original_path) if exists
-pub fn get_project_profile_info(project_dir: &PathBuf) -> Result<Option<(String, PathBuf)>> {
-    let path = project_profile_path(project_dir);
-    if !path.exists() {
-        return Ok(None);
-    }
-    let content = fs::read_to_string(&path)
-        .with_context(|| format!("reading project profile from {}", path.display()))?;
+/// A project directory's profile mapping, as stored in `projects/<hash>.json`
+#[derive(Debug, Clone)]
+pub struct ProjectMapping {
+    /// The mapping file itself
+    pub file: PathBuf,
+    /// The project directory it applies to
+    pub path: PathBuf,
+    pub profile: String,
+    /// Which project settings file the profile was written to
+    pub target: ProjectTarget,
+    /// What the switch wrote to each settings file, keyed by file name
+    pub managed: BTreeMap<String, ManagedKeys>,
+}
+
+/// The keys a project switch wrote to one settings file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ManagedKeys {
+    /// The profile's values as written (with secret references, never resolved secrets)
+    pub applied: Value,
+    /// The values those keys had before the switch; keys missing here were added
+    pub previous: Value,
+}
+
+/// Parse a mapping file; `None` if it lacks the profile or path
+fn read_project_mapping(file: &Path) -> Result<Option<ProjectMapping>> {
+    let content = fs::read_to_string(file)
+        .with_context(|| format!("reading project profile from {}", file.display()))?;
     let value: serde_json::Value = serde_json::from_str(&content)
-        .with_context(|| format!("parsing project profile JSON from {}", path.display()))?;
+        .with_context(|| format!("parsing project profile JSON from {}", file.display()))?;
 
     let profile_name = value
         .get("profile")
@@ -100,19 +280,92 @@ pub fn get_project_profile_info(project_dir: &PathBuf) -> Result<Option<(String,
         .and_then(|v| v.as_str())
         .map(PathBuf::from);
 
+    let target = value
+        .get("target")
+        .and_then(|v| v.as_str())
+        .and_then(|s| ProjectTarget::from_str(s, false).ok())
+        .unwrap_or_default();
+    // Mappings written before switches were recorded have no managed keys
+    let managed = value
+        .get("managed")
+        .and_then(|v| serde_json::from_value(v.clone()).ok())
+        .unwrap_or_default();
+
     match (profile_name, original_path) {
-        (Some(name), Some(path)) => Ok(Some((name, path))),
+        (Some(profile), Some(path)) => Ok(Some(ProjectMapping {
+            file: file.to_path_buf(),
+            path,
+            profile,
+            target,
+            managed,
+        })),
         _ => Ok(None),
     }
 }
 
-/// Set the project profile for a specific directory
-pub fn set_project_profile(project_dir: &PathBuf, profile_name: &str) -> Result<()> {
+/// Get the project profile mapping of a directory, if it has one
+pub fn get_project_mapping(project_dir: &Path) -> Result<Option<ProjectMapping>> {
+    let path = project_profile_path(project_dir);
+    if !path.exists() {
+        return Ok(None);
+    }
+    read_project_mapping(&path)
+}
+
+/// Get the project profile info (profile name and original path) for a directory
+/// Returns (profile_name, original_path) if exists
+pub fn get_project_profile_info(project_dir: &Path) -> Result<Option<(String, PathBuf)>> {
+    Ok(get_project_mapping(project_dir)?.map(|m| (m.profile, m.path)))
+}
+
+/// List every project profile mapping, sorted by project path
+pub fn list_project_mappings() -> Result<Vec<ProjectMapping>> {
+    let dir = project_profiles_dir();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    migrate_project_mappings_once();
+
+    let mut mappings = Vec::new();
+    for entry in fs::read_dir(&dir).with_context(|| format!("reading {}", dir.display()))? {
+        let file = entry?.path();
+        if file.extension().and_then(|e| e.to_str()) == Some("json")
+            && let Some(mapping) = read_project_mapping(&file)?
+        {
+            mappings.push(mapping);
+        }
+    }
+    mappings.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(mappings)
+}
+
+/// Find the nearest directory at or above `dir` that has a project profile mapping
+/// Returns (mapped_dir, profile_name) if found
+pub fn find_project_mapping(dir: &Path) -> Result<Option<(PathBuf, String)>> {
+    for ancestor in canonical_project_dir(dir).ancestors() {
+        let ancestor = ancestor.to_path_buf();
+        if let Some((profile_name, _)) = get_project_profile_info(&ancestor)? {
+            return Ok(Some((ancestor, profile_name)));
+        }
+    }
+    Ok(None)
+}
+
+/// Set the project profile for a specific directory, recording what the
+/// switch wrote so it can be undone exactly
+pub fn set_project_profile(
+    project_dir: &Path,
+    profile_name: &str,
+    target: ProjectTarget,
+    managed: &BTreeMap<String, ManagedKeys>,
+) -> Result<()> {
     ensure_project_profiles_dir()?;
     let path = project_profile_path(project_dir);
     let content = serde_json::json!({
         "profile": profile_name,
-        "path": project_dir.to_string_lossy()
+        "path": canonical_project_dir(project_dir).to_string_lossy(),
+        "target": target.as_str(),
+        "managed": managed
     });
     fs::write(&path, serde_json::to_string_pretty(&content)?)
         .with_context(|| format!("writing project profile to {}", path.display()))?;
@@ -120,7 +373,7 @@ pub fn set_project_profile(project_dir: &PathBuf, profile_name: &str) -> Result<
 }
 
 /// Remove the project profile for a specific directory
-pub fn remove_project_profile(project_dir: &PathBuf) -> Result<()> {
+pub fn remove_project_profile(project_dir: &Path) -> Result<()> {
     let path = project_profile_path(project_dir);
     if path.exists() {
         fs::remove_file(&path)
@@ -144,3 +397,8 @@ pub fn claude_settings_path() -> PathBuf {
 pub fn profile_path(name: &str) -> PathBuf {
     profiles_dir().join(format!("{}.json", name))
 }
+
+/// Get the path of a profile's metadata sidecar, hidden so it is not listed as a profile
+pub fn profile_meta_path(name: &str) -> PathBuf {
+    profiles_dir().join(format!(".{}.meta.json", name))
+}
@@ -0,0 +1,210 @@
+use anyhow::{Result, bail};
+use crossterm::style::Stylize;
+use serde_json::{Value, json};
+use std::env;
+use std::fmt;
+use std::io::{self, IsTerminal};
+
+use crate::config::{claude_settings_path, current_project_root, profile_path};
+use crate::model::{Profile, SwitchMode};
+use crate::output::{OutputFormat, emit};
+use crate::profile::{
+    current_profile_settings, get_current_profile, owned_subset, read_claude_settings,
+    read_project_settings,
+};
+use crate::redact::{redact_settings, redact_value};
+use crate::say;
+use crate::secret::restore_refs;
+use crate::snapshot::{KeyPath, flatten, load_snapshot, path_string};
+
+/// How a single key path differs between two settings values
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added(Value),
+    Removed(Value),
+    Changed(Value, Value),
+}
+
+/// One differing key path, e.g. `env.ANTHROPIC_MODEL`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffEntry {
+    pub path: KeyPath,
+    pub change: Change,
+}
+
+/// Compare two settings values key path by key path
+pub fn diff(old: &Value, new: &Value) -> Vec<DiffEntry> {
+    let old = flatten(old);
+    let mut new = flatten(new);
+    let mut entries = Vec::new();
+
+    for (path, old_value) in old {
+        let change = match new.remove(&path) {
+            None => Change::Removed(old_value),
+            Some(new_value) if new_value != old_value => Change::Changed(old_value, new_value),
+            Some(_) => continue,
+        };
+        entries.push(DiffEntry { path, change });
+    }
+    entries.extend(new.into_iter().map(|(path, value)| DiffEntry {
+        path,
+        change: Change::Added(value),
+    }));
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    entries
+}
+
+/// Format a value for display, redacting credentials (secret references are shown as-is)
+pub fn format_value(path: &[String], value: &Value) -> String {
+    let value = match path.last() {
+        Some(key) => redact_value(key, value),
+        None => {
+            let mut value = value.clone();
+            redact_settings(&mut value);
+            value
+        }
+    };
+    match value {
+        Value::String(s) => s,
+        other => other.to_string(),
+    }
+}
+
+fn use_color() -> bool {
+    io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none()
+}
+
+impl fmt::Display for DiffEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = path_string(&self.path);
+        let (sign, text) = match &self.change {
+            Change::Added(v) => ('+', format!("{}: {}", path, format_value(&self.path, v))),
+            Change::Removed(v) => ('-', format!("{}: {}", path, format_value(&self.path, v))),
+            Change::Changed(old, new) => (
+                '~',
+                format!(
+                    "{}: {} → {}",
+                    path,
+                    format_value(&self.path, old),
+                    format_value(&self.path, new)
+                ),
+            ),
+        };
+        let line = format!("{} {}", sign, text);
+        if !use_color() {
+            return write!(f, "{}", line);
+        }
+        match sign {
+            '+' => write!(f, "{}", line.green()),
+            '-' => write!(f, "{}", line.red()),
+            _ => write!(f, "{}", line.yellow()),
+        }
+    }
+}
+
+impl DiffEntry {
+    /// The entry for machine-readable output, with credentials redacted
+    fn to_json(&self) -> Value {
+        let redact = |value: &Value| match self.path.last() {
+            Some(key) => redact_value(key, value),
+            None => {
+                let mut value = value.clone();
+                redact_settings(&mut value);
+                value
+            }
+        };
+        let (change, old, new) = match &self.change {
+            Change::Added(v) => ("added", None, Some(redact(v))),
+            Change::Removed(v) => ("removed", Some(redact(v)), None),
+            Change::Changed(old, new) => ("changed", Some(redact(old)), Some(redact(new))),
+        };
+        json!({
+            "path": path_string(&self.path),
+            "change": change,
+            "old": old,
+            "new": new,
+        })
+    }
+}
+
+/// Print diff entries one per line, indented
+pub fn print_diff(entries: &[DiffEntry]) {
+    for entry in entries {
+        say!("  {}", entry);
+    }
+}
+
+/// What `ccm diff` compares a profile against
+pub enum DiffTarget {
+    Profile(String),
+    Settings,
+    Project,
+}
+
+/// Load a profile with `extends` flattened, as the settings it would apply
+fn profile_settings(name: &str) -> Result<Profile> {
+    if !profile_path(name).exists() {
+        bail!("Profile '{}' does not exist", name);
+    }
+    Profile::load_resolved(name)
+}
+
+/// Print how a profile differs from another profile, the global settings or the project settings
+pub fn diff_command(name: &str, target: DiffTarget, format: OutputFormat) -> Result<()> {
+    let profile = profile_settings(name)?;
+    let profile_value = profile.to_settings_value();
+
+    let (label, other) = match target {
+        DiffTarget::Profile(other) => {
+            let value = profile_settings(&other)?.to_settings_value();
+            (format!("profile '{}'", other), value)
+        }
+        DiffTarget::Settings => {
+            let settings = read_claude_settings()?;
+            let value = if get_current_profile()?.as_deref() == Some(name) {
+                current_profile_settings(&profile, load_snapshot(name)?.as_ref(), &settings)?
+            } else {
+                let mut value = if profile.switch_mode == Some(SwitchMode::Merge) {
+                    owned_subset(&settings, &profile_value)
+                } else {
+                    settings
+                };
+                restore_refs(&mut value, &profile_value);
+                value
+            };
+            (claude_settings_path().display().to_string(), value)
+        }
+        DiffTarget::Project => {
+            let root = current_project_root()?;
+            let mut value = match read_project_settings(&root)? {
+                Some(settings) => owned_subset(&settings, &profile_value),
+                None => Value::Object(Default::default()),
+            };
+            restore_refs(&mut value, &profile_value);
+            (
+                format!("project settings in {}", root.join(".claude").display()),
+                value,
+            )
+        }
+    };
+
+    let entries = diff(&profile_value, &other);
+    if !format.is_text() {
+        let changes: Vec<Value> = entries.iter().map(DiffEntry::to_json).collect();
+        return emit(
+            format,
+            &json!({
+                "profile": name,
+                "against": label,
+                "changes": changes,
+            }),
+        );
+    }
+    if entries.is_empty() {
+        say!("✓ Profile '{}' matches {}", name, label);
+        return Ok(());
+    }
+    say!("Profile '{}' → {}:", name, label);
+    print_diff(&entries);
+    Ok(())
+}
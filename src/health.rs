@@ -0,0 +1,273 @@
+use anyhow::{Result, anyhow, bail};
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use std::time::{Duration, Instant};
+
+use crate::config::profile_path;
+use crate::model::{API_KEY, MODEL, Profile, SMALL_FAST_MODEL, SecretSource};
+use crate::output::{OutputFormat, Reported, emit};
+use crate::redact::redact_text;
+use crate::say;
+use crate::secret::resolve_secrets;
+use crate::update::http_client;
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+/// Model Claude Code falls back to when the profile sets no ANTHROPIC_MODEL
+const DEFAULT_MODEL: &str = "claude-sonnet-4-5";
+/// Request timeout when the profile sets no API_TIMEOUT_MS
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How a single test request turned out
+#[derive(Debug)]
+pub enum Outcome {
+    /// The endpoint answered; `model` is the model name it reported back
+    Ok {
+        model: Option<String>,
+    },
+    AuthError(String),
+    ModelRejected(String),
+    HttpError(String),
+    Unreachable(String),
+}
+
+/// Result of testing one model against the profile's endpoint
+#[derive(Debug)]
+pub struct ModelCheck {
+    pub model: String,
+    /// Which env key the model came from (or "default")
+    pub source: &'static str,
+    pub status: Option<StatusCode>,
+    pub latency: Duration,
+    pub outcome: Outcome,
+}
+
+impl ModelCheck {
+    pub fn passed(&self) -> bool {
+        matches!(self.outcome, Outcome::Ok { .. })
+    }
+
+    /// The check for machine-readable output
+    fn to_json(&self) -> Value {
+        let (result, message, served_as) = match &self.outcome {
+            Outcome::Ok { model } => ("ok", None, model.as_deref()),
+            Outcome::AuthError(msg) => ("auth_error", Some(msg), None),
+            Outcome::ModelRejected(msg) => ("model_rejected", Some(msg), None),
+            Outcome::HttpError(msg) => ("http_error", Some(msg), None),
+            Outcome::Unreachable(msg) => ("unreachable", Some(msg), None),
+        };
+        json!({
+            "model": self.model,
+            "source": self.source,
+            "status": self.status.map(|s| s.as_u16()),
+            "latency_ms": self.latency.as_millis() as u64,
+            "result": result,
+            "message": message,
+            "served_as": served_as,
+        })
+    }
+}
+
+/// Pull the human-readable message out of an Anthropic-style error body
+fn error_message(body: &str) -> String {
+    let message = serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|v| {
+            v.pointer("/error/message")
+                .or_else(|| v.get("message"))
+                .and_then(|m| m.as_str())
+                .map(|s| s.to_string())
+        })
+        .unwrap_or_else(|| body.chars().take(200).collect());
+    redact_text(&message)
+}
+
+fn classify(status: StatusCode, body: &str) -> Outcome {
+    if status.is_success() {
+        let model = serde_json::from_str::<Value>(body)
+            .ok()
+            .and_then(|v| v.get("model").and_then(|m| m.as_str()).map(String::from));
+        return Outcome::Ok { model };
+    }
+    let message = error_message(body);
+    match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Outcome::AuthError(message),
+        StatusCode::BAD_REQUEST | StatusCode::NOT_FOUND | StatusCode::UNPROCESSABLE_ENTITY
+            if message.to_lowercase().contains("model") =>
+        {
+            Outcome::ModelRejected(message)
+        }
+        _ => Outcome::HttpError(message),
+    }
+}
+
+/// Send a minimal `/v1/messages` request for one model
+fn check_model(
+    client: &reqwest::blocking::Client,
+    url: &str,
+    auth_token: Option<&str>,
+    api_key: Option<&str>,
+    model: &str,
+) -> (Option<StatusCode>, Duration, Outcome) {
+    let body = json!({
+        "model": model,
+        "max_tokens": 1,
+        "messages": [{ "role": "user", "content": "ping" }],
+    });
+    let mut request = client
+        .post(url)
+        .header("anthropic-version", ANTHROPIC_VERSION)
+        .json(&body);
+    if let Some(token) = auth_token {
+        request = request.bearer_auth(token);
+    }
+    if let Some(key) = api_key {
+        request = request.header("x-api-key", key);
+    }
+
+    let started = Instant::now();
+    let result = request.send().and_then(|r| {
+        let status = r.status();
+        r.text().map(|text| (status, text))
+    });
+    let latency = started.elapsed();
+
+    match result {
+        Ok((status, text)) => (Some(status), latency, classify(status, &text)),
+        Err(e) => (
+            None,
+            latency,
+            Outcome::Unreachable(redact_text(&e.to_string())),
+        ),
+    }
+}
+
+/// Test every model a profile uses against its endpoint
+pub fn check_profile(profile: &Profile) -> Result<(String, Vec<ModelCheck>)> {
+    let env = &profile.env;
+    let base_url = env
+        .base_url
+        .as_deref()
+        .ok_or_else(|| anyhow!("Profile has no ANTHROPIC_BASE_URL to test"))?;
+    let url = format!("{}/v1/messages", base_url.trim_end_matches('/'));
+
+    let auth_token = match &env.auth_token {
+        Some(SecretSource::Text(token)) => Some(token.as_str()),
+        _ => None,
+    };
+    let api_key = env.extra.get(API_KEY).and_then(|v| v.as_str());
+
+    let timeout = env
+        .api_timeout_ms
+        .and_then(|t| u64::try_from(t.value).ok())
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_TIMEOUT);
+    let client = http_client(concat!("ccm/", env!("CARGO_PKG_VERSION")), Some(timeout))?;
+
+    let mut models = vec![(
+        env.model
+            .clone()
+            .unwrap_or_else(|| DEFAULT_MODEL.to_string()),
+        if env.model.is_some() {
+            MODEL
+        } else {
+            "default"
+        },
+    )];
+    if let Some(small) = &env.small_fast_model
+        && !models.iter().any(|(m, _)| m == small)
+    {
+        models.push((small.clone(), SMALL_FAST_MODEL));
+    }
+
+    let checks = models
+        .into_iter()
+        .map(|(model, source)| {
+            let (status, latency, outcome) =
+                check_model(&client, &url, auth_token, api_key, &model);
+            ModelCheck {
+                model,
+                source,
+                status,
+                latency,
+                outcome,
+            }
+        })
+        .collect();
+    Ok((url, checks))
+}
+
+/// Check that a profile's endpoint, token and models actually work
+pub fn test_profile(name: &str, format: OutputFormat) -> Result<()> {
+    if !profile_path(name).exists() {
+        bail!("Profile '{}' does not exist", name);
+    }
+    let profile = resolve_secrets(&Profile::load_resolved(name)?)?;
+    if profile.env.auth_token.is_none() && !profile.env.extra.contains_key(API_KEY) {
+        say!(
+            "⚠️  Profile '{}' has no ANTHROPIC_AUTH_TOKEN or ANTHROPIC_API_KEY",
+            name
+        );
+    }
+
+    let (url, checks) = check_profile(&profile)?;
+    let failed = checks.iter().filter(|c| !c.passed()).count();
+    if !format.is_text() {
+        let results: Vec<Value> = checks.iter().map(ModelCheck::to_json).collect();
+        emit(
+            format,
+            &json!({
+                "profile": name,
+                "url": redact_text(&url),
+                "passed": failed == 0,
+                "checks": results,
+            }),
+        )?;
+        // The result already says it failed; exit like the text output would
+        if failed > 0 {
+            return Err(Reported.into());
+        }
+        return Ok(());
+    }
+    say!("Testing profile '{}' against {}", name, url);
+
+    for check in &checks {
+        let status = check
+            .status
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| "no response".to_string());
+        let summary = format!(
+            "{} ({}): {} in {} ms",
+            check.model,
+            check.source,
+            status,
+            check.latency.as_millis()
+        );
+        match &check.outcome {
+            Outcome::Ok { model } => match model {
+                Some(reported) if reported != &check.model => {
+                    say!("  ✓ {} (served as '{}')", summary, reported)
+                }
+                _ => say!("  ✓ {}", summary),
+            },
+            Outcome::AuthError(msg) => say!("  ✗ {} - authentication failed: {}", summary, msg),
+            Outcome::ModelRejected(msg) => {
+                say!("  ✗ {} - model not accepted: {}", summary, msg)
+            }
+            Outcome::HttpError(msg) => say!("  ✗ {} - {}", summary, msg),
+            Outcome::Unreachable(msg) => {
+                say!("  ✗ {} - endpoint unreachable: {}", summary, msg)
+            }
+        }
+    }
+
+    if failed > 0 {
+        bail!(
+            "Profile '{}' failed {} of {} checks",
+            name,
+            failed,
+            checks.len()
+        );
+    }
+    say!("✓ Profile '{}' is working", name);
+    Ok(())
+}
@@ -0,0 +1,166 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::collections::BTreeMap;
+use std::env;
+
+use crate::config::{find_project_mapping, get_current_working_dir};
+use crate::model::Profile;
+use crate::secret::resolve_secrets_unattended;
+use crate::shell::{Shell, export_line, unset_line, validate_env_key};
+
+/// Directory whose project profile is currently applied by the hook
+const HOOK_DIR: &str = "CCM_HOOK_DIR";
+/// Profile currently applied by the hook
+const HOOK_PROFILE: &str = "CCM_HOOK_PROFILE";
+/// JSON object of the values the hook's keys had before it exported them
+/// (`null` when unset), to put back when leaving
+const HOOK_SAVED: &str = "CCM_HOOK_SAVED";
+
+/// Shells with a prompt hook
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HookShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl From<HookShell> for Shell {
+    fn from(shell: HookShell) -> Self {
+        match shell {
+            HookShell::Bash => Self::Bash,
+            HookShell::Zsh => Self::Zsh,
+            HookShell::Fish => Self::Fish,
+        }
+    }
+}
+
+/// Quote the ccm binary path for embedding in a hook script
+fn ccm_binary(shell: HookShell) -> Result<String> {
+    let exe = env::current_exe().context("getting the ccm executable path")?;
+    let exe = exe.to_string_lossy();
+    Ok(match shell {
+        HookShell::Fish => format!("'{}'", exe.replace('\\', r"\\").replace('\'', r"\'")),
+        _ => format!("'{}'", exe.replace('\'', r"'\''")),
+    })
+}
+
+/// Print the prompt hook for a shell, to be evaluated from its rc file
+pub fn print_hook(shell: HookShell) -> Result<()> {
+    let ccm = ccm_binary(shell)?;
+    let script = match shell {
+        HookShell::Bash => format!(
+            r#"_ccm_hook() {{
+  local previous_exit_status=$?
+  eval "$({ccm} hook-env --shell bash)"
+  return $previous_exit_status
+}}
+if [[ ";${{PROMPT_COMMAND[*]:-}};" != *";_ccm_hook;"* ]]; then
+  PROMPT_COMMAND="_ccm_hook${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}"
+fi"#
+        ),
+        HookShell::Zsh => format!(
+            r#"_ccm_hook() {{
+  eval "$({ccm} hook-env --shell zsh)"
+}}
+typeset -ag precmd_functions chpwd_functions
+if (( ! ${{precmd_functions[(I)_ccm_hook]}} )); then
+  precmd_functions=(_ccm_hook $precmd_functions)
+fi
+if (( ! ${{chpwd_functions[(I)_ccm_hook]}} )); then
+  chpwd_functions=(_ccm_hook $chpwd_functions)
+fi"#
+        ),
+        HookShell::Fish => format!(
+            r#"function __ccm_hook --on-event fish_prompt
+    {ccm} hook-env --shell fish | source
+end"#
+        ),
+    };
+    println!("{}", script);
+    Ok(())
+}
+
+/// The values saved by the hook when it entered the applied project
+fn saved_values() -> Result<BTreeMap<String, Option<String>>> {
+    match env::var(HOOK_SAVED) {
+        Ok(saved) => {
+            serde_json::from_str(&saved).with_context(|| format!("parsing {}", HOOK_SAVED))
+        }
+        Err(_) => Ok(BTreeMap::new()),
+    }
+}
+
+/// Compute the commands that bring the shell env in line with the project
+/// mapping of the current directory (or its nearest mapped parent)
+fn hook_env_lines(shell: Shell) -> Result<Vec<String>> {
+    let cwd = get_current_working_dir()?;
+    let target = find_project_mapping(&cwd)?;
+
+    let applied_dir = env::var(HOOK_DIR).ok();
+    let applied_profile = env::var(HOOK_PROFILE).ok();
+    if let Some((dir, profile)) = &target
+        && applied_dir.as_deref() == Some(&*dir.to_string_lossy())
+        && applied_profile.as_deref() == Some(profile.as_str())
+    {
+        return Ok(Vec::new());
+    }
+    if target.is_none() && applied_dir.is_none() {
+        return Ok(Vec::new());
+    }
+
+    let mut lines = Vec::new();
+
+    // Leaving (or switching) a project: put back what we exported over
+    let mut saved = BTreeMap::new();
+    if applied_dir.is_some() {
+        saved = saved_values()?;
+        for (key, previous) in &saved {
+            validate_env_key(key)?;
+            lines.push(match previous {
+                Some(value) => export_line(shell, key, value),
+                None => unset_line(shell, key)?,
+            });
+        }
+        for key in [HOOK_DIR, HOOK_PROFILE, HOOK_SAVED] {
+            lines.push(unset_line(shell, key)?);
+        }
+    }
+
+    if let Some((dir, profile_name)) = target {
+        let profile = resolve_secrets_unattended(&Profile::load_resolved(&profile_name)?)?;
+        let mut previous = BTreeMap::new();
+        for (key, value) in profile.env.to_env_vars() {
+            validate_env_key(&key)?;
+            lines.push(export_line(shell, &key, &value));
+            // The value from before any project, not the one of the project being left
+            let before = match saved.get(&key) {
+                Some(before) => before.clone(),
+                None => env::var(&key).ok(),
+            };
+            previous.insert(key, before);
+        }
+        lines.push(export_line(shell, HOOK_DIR, &dir.to_string_lossy()));
+        lines.push(export_line(shell, HOOK_PROFILE, &profile_name));
+        lines.push(export_line(
+            shell,
+            HOOK_SAVED,
+            &serde_json::to_string(&previous)?,
+        ));
+    }
+
+    Ok(lines)
+}
+
+/// Called by the prompt hook. Errors are reported on stderr and produce no
+/// output, so a broken profile never breaks the user's prompt.
+pub fn print_hook_env(shell: Shell) -> Result<()> {
+    match hook_env_lines(shell) {
+        Ok(lines) => {
+            for line in lines {
+                println!("{}", line);
+            }
+        }
+        Err(e) => eprintln!("ccm: {:#}", e),
+    }
+    Ok(())
+}
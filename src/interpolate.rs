@@ -0,0 +1,97 @@
+use anyhow::{Result, bail};
+use serde_json::Value;
+use std::env;
+
+/// One piece of an env value: literal text or a `${VAR}` placeholder
+enum Segment<'a> {
+    Text(&'a str),
+    Var {
+        name: &'a str,
+        default: Option<&'a str>,
+    },
+}
+
+/// Whether an env value has placeholders (or `$${` escapes) to expand when applied
+pub fn has_placeholders(value: &Value) -> bool {
+    value.as_str().is_some_and(|s| s.contains("${"))
+}
+
+/// Split text into literals and placeholders: `${VAR}`, `${env:VAR}` and
+/// `${VAR:-default}`. `$${` is a literal `${`.
+fn parse(text: &str) -> Result<Vec<Segment<'_>>> {
+    let mut segments = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            segments.push(Segment::Text(&rest[..start - 1]));
+            segments.push(Segment::Text("${"));
+            rest = &rest[start + 2..];
+            continue;
+        }
+        segments.push(Segment::Text(&rest[..start]));
+        let after = &rest[start + 2..];
+        let Some(end) = after.find('}') else {
+            bail!("unterminated placeholder '${{{}'", after);
+        };
+        let inner = &after[..end];
+        let spec = inner.strip_prefix("env:").unwrap_or(inner);
+        let (name, default) = match spec.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (spec, None),
+        };
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            bail!("invalid placeholder '${{{}}}'", inner);
+        }
+        segments.push(Segment::Var { name, default });
+        rest = &after[end + 1..];
+    }
+    segments.push(Segment::Text(rest));
+    Ok(segments)
+}
+
+/// Check placeholder syntax without expanding anything
+pub fn check_placeholders(text: &str) -> Result<()> {
+    parse(text).map(|_| ())
+}
+
+/// Whether `value` could be an expansion of `text`: its literal parts appear
+/// as written, and each placeholder stands for any text. Without knowing the
+/// environment it was expanded in, this is all that can be told.
+pub fn matches_template(text: &str, value: &str) -> bool {
+    fn matches(segments: &[Segment], value: &str) -> bool {
+        match segments.split_first() {
+            None => value.is_empty(),
+            Some((Segment::Text(text), rest)) => value
+                .strip_prefix(text)
+                .is_some_and(|value| matches(rest, value)),
+            Some((Segment::Var { .. }, rest)) => (0..=value.len())
+                .filter(|&i| value.is_char_boundary(i))
+                .any(|i| matches(rest, &value[i..])),
+        }
+    }
+    parse(text).is_ok_and(|segments| matches(&segments, value))
+}
+
+/// Expand placeholders from the environment. A default is used when the
+/// variable is unset or empty; without one, an unset variable is an error.
+pub fn expand(text: &str) -> Result<String> {
+    let mut expanded = String::with_capacity(text.len());
+    for segment in parse(text)? {
+        match segment {
+            Segment::Text(text) => expanded.push_str(text),
+            Segment::Var { name, default } => match (env::var(name), default) {
+                (Ok(value), Some(default)) if value.is_empty() => expanded.push_str(default),
+                (Ok(value), _) => expanded.push_str(&value),
+                (Err(_), Some(default)) => expanded.push_str(default),
+                (Err(_), None) => bail!(
+                    "environment variable {} is not set (use ${{{}:-default}} for a fallback)",
+                    name,
+                    name
+                ),
+            },
+        }
+    }
+    Ok(expanded)
+}
@@ -1,7 +1,23 @@
 pub mod cli;
 pub mod config;
+pub mod diff;
+pub mod health;
+pub mod hook;
+pub mod interpolate;
+pub mod meta;
+pub mod model;
+pub mod output;
+pub mod preset;
 pub mod profile;
+pub mod projects;
+pub mod redact;
+pub mod secret;
+pub mod shell;
+pub mod snapshot;
+pub mod status;
 pub mod tui;
 pub mod update;
+pub mod validate;
+pub mod vault;
 
 pub use cli::Cli;
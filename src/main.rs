@@ -1,25 +1,142 @@
 use anyhow::Result;
 use ccm::{
-    cli::{Cli, Commands},
-    profile, tui,
+    cli::{Cli, Commands, ProjectsCommands, SecretCommands},
+    diff::{self, DiffTarget},
+    health, hook, meta,
+    output::{self, OutputFormat},
+    preset, profile, projects,
+    redact::redact_text,
+    shell, status, tui, validate, vault,
 };
 use clap::{CommandFactory, Parser};
+use serde_json::{Value, json};
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
+    if let Err(e) = run(&cli) {
+        if e.is::<output::Reported>() {
+            // Already part of the printed result
+        } else if cli.output.is_text() {
+            // As if returned from main, with any credentials in it masked
+            eprintln!("Error: {}", redact_text(&format!("{:?}", e)));
+        } else {
+            // Structured output: errors are printed in a stable shape too
+            output::emit_error(cli.output, &e)?;
+        }
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Print the result of a command that changes something, when asked for
+/// structured output (its messages went to stderr)
+fn report(format: OutputFormat, result: Value) -> Result<()> {
+    if format.is_text() {
+        return Ok(());
+    }
+    output::emit(format, &result)
+}
+
+fn run(cli: &Cli) -> Result<()> {
+    let format = cli.output;
+    if !format.is_text()
+        && let Some(command) = &cli.command
+        && !command.supports_structured_output()
+    {
+        anyhow::bail!(
+            "--output {} is not supported by ui, hook and hook-env",
+            format.as_str()
+        );
+    }
+    output::set_format(format);
+
     match &cli.command {
-        Some(Commands::Add { name, env }) => {
-            profile::add_profile_interactive(name, env)?;
-        }
-        Some(Commands::List) => profile::list_profiles()?,
-        Some(Commands::Show { name }) => profile::show_profile(name)?,
-        Some(Commands::Remove { name }) => profile::remove_profile(name)?,
-        Some(Commands::Switch { name, project }) => profile::switch_to_profile(name, *project)?,
-        Some(Commands::Run) => profile::launch_claude_code()?,
-        Some(Commands::Import { name }) => profile::import_current_profile(name)?,
-        Some(Commands::Rename { origin, new }) => profile::rename_profile(origin, new)?,
-        Some(Commands::Edit { name }) => profile::edit_profile(name)?,
+        Some(Commands::Add { name, args }) => {
+            profile::add_profile(name, args)?;
+            report(format, json!({ "command": "add", "profile": name }))?;
+        }
+        Some(Commands::List { args }) => profile::list_profiles(args, format)?,
+        Some(Commands::Describe {
+            name,
+            description,
+            tag,
+            untag,
+            provider,
+        }) => meta::describe_profile(
+            name,
+            description.as_deref(),
+            tag,
+            untag,
+            provider.as_deref(),
+            format,
+        )?,
+        Some(Commands::Presets) => preset::list_presets(format)?,
+        Some(Commands::Show {
+            name,
+            resolved,
+            reveal,
+        }) => profile::show_profile(name, *resolved, *reveal, format)?,
+        Some(Commands::Status) => status::show_status(format)?,
+        Some(Commands::Validate { name }) => validate::validate_profiles(name.as_deref(), format)?,
+        Some(Commands::Diff {
+            name,
+            other,
+            project,
+            ..
+        }) => {
+            let target = match other {
+                Some(other) => DiffTarget::Profile(other.clone()),
+                None if *project => DiffTarget::Project,
+                None => DiffTarget::Settings,
+            };
+            diff::diff_command(name, target, format)?;
+        }
+        Some(Commands::Remove { name }) => {
+            let removed = profile::remove_profile(name)?;
+            report(
+                format,
+                json!({ "command": "remove", "profile": name, "changed": removed }),
+            )?;
+        }
+        Some(Commands::Switch {
+            name,
+            project,
+            mode,
+            target,
+            no_validate,
+        }) => {
+            let project_target = project.then(|| target.unwrap_or_default());
+            let switched = profile::switch_to_profile(name, project_target, *mode, !no_validate)?;
+            report(
+                format,
+                json!({
+                    "command": "switch",
+                    "profile": name,
+                    "project": project,
+                    "changed": switched,
+                }),
+            )?;
+        }
+        Some(Commands::Run { profile, args }) => {
+            profile::run_claude_code(profile.as_deref(), args)?
+        }
+        Some(Commands::Test { name }) => health::test_profile(name, format)?,
+        Some(Commands::Import { name }) => {
+            profile::import_current_profile(name)?;
+            report(format, json!({ "command": "import", "profile": name }))?;
+        }
+        Some(Commands::Rename { origin, new }) => {
+            profile::rename_profile(origin, new)?;
+            report(
+                format,
+                json!({ "command": "rename", "profile": new, "from": origin }),
+            )?;
+        }
+        Some(Commands::Edit { name, no_validate }) => {
+            profile::edit_profile(name, !no_validate)?;
+            report(format, json!({ "command": "edit", "profile": name }))?;
+        }
         Some(Commands::Ui) => match tui::launch_tui() {
             Ok(_) => {}
             Err(e) => {
@@ -42,15 +159,50 @@ fn main() -> Result<()> {
         Some(Commands::TestTui) => {
             tui::test_tui_components()?;
         }
-        Some(Commands::Sync) => {
-            profile::sync_profile()?;
+        Some(Commands::Sync { project }) => {
+            let changed = if *project {
+                profile::sync_project_profile()?
+            } else {
+                profile::sync_profile()?
+            };
+            report(
+                format,
+                json!({ "command": "sync", "project": project, "changed": changed }),
+            )?;
         }
         Some(Commands::Update { check }) => {
-            ccm::update::update_self(*check)?;
+            ccm::update::update_self(*check, format)?;
         }
         Some(Commands::ClearProject) => {
-            profile::clear_project_profile()?;
+            let cleared = profile::clear_project_profile()?;
+            report(
+                format,
+                json!({ "command": "clear-project", "changed": cleared }),
+            )?;
+        }
+        Some(Commands::Env { name, shell, unset }) => {
+            shell::print_profile_env(name, *shell, *unset, format)?;
         }
+        Some(Commands::Hook { shell }) => hook::print_hook(*shell)?,
+        Some(Commands::HookEnv { shell }) => hook::print_hook_env((*shell).into())?,
+        Some(Commands::Projects { action }) => match action {
+            ProjectsCommands::List => projects::list_projects(format)?,
+            ProjectsCommands::Prune => projects::prune_projects(format)?,
+            ProjectsCommands::Clear { path } => {
+                let cleared = projects::clear_project(path)?;
+                report(
+                    format,
+                    json!({ "command": "projects clear", "path": path, "changed": cleared }),
+                )?;
+            }
+        },
+        Some(Commands::Secret { action }) => match action {
+            SecretCommands::Set { name, stdin } => vault::set_secret(name, *stdin, format)?,
+            SecretCommands::Get { name } => vault::get_secret(name, format)?,
+            SecretCommands::Remove { name } => vault::remove_secret(name, format)?,
+            SecretCommands::List => vault::list_secrets(format)?,
+            SecretCommands::Migrate => vault::migrate_profile_tokens(format)?,
+        },
         None => {
             // If no subcommand is provided, print help
             Cli::command().print_help()?;
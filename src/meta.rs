@@ -0,0 +1,198 @@
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::time::SystemTime;
+
+use crate::config::{ensure_profiles_dir, profile_meta_path, profile_path};
+use crate::output::{OutputFormat, emit};
+use crate::say;
+
+/// What ccm knows about a profile besides its settings. Kept in a hidden
+/// sidecar file, so none of it is copied into Claude's settings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileMeta {
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    /// The provider preset the profile was created from, or set by `ccm describe`
+    pub provider: Option<String>,
+    /// RFC 3339 timestamps in UTC
+    pub created_at: Option<String>,
+    pub last_switched_at: Option<String>,
+    pub switch_count: u64,
+}
+
+/// Order of `ccm ls`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ProfileSort {
+    #[default]
+    Name,
+    /// Most recently switched to first
+    LastUsed,
+    /// Most often switched to first
+    MostUsed,
+    /// Newest first
+    Created,
+}
+
+fn now() -> String {
+    humantime::format_rfc3339_seconds(SystemTime::now()).to_string()
+}
+
+/// Shorten an RFC 3339 timestamp for display, e.g. `2025-10-16 09:12 UTC`
+pub fn display_time(timestamp: &str) -> String {
+    match timestamp.get(..16) {
+        Some(minutes) => format!("{} UTC", minutes.replace('T', " ")),
+        None => timestamp.to_string(),
+    }
+}
+
+/// e.g. `1 switch`, `3 switches`
+pub fn switches(count: u64) -> String {
+    if count == 1 {
+        "1 switch".to_string()
+    } else {
+        format!("{} switches", count)
+    }
+}
+
+/// Load a profile's metadata; profiles without a sidecar have none yet
+pub fn load_meta(name: &str) -> Result<ProfileMeta> {
+    let path = profile_meta_path(name);
+    if !path.exists() {
+        return Ok(ProfileMeta::default());
+    }
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("reading profile metadata {}", path.display()))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("parsing profile metadata {}", path.display()))
+}
+
+pub fn save_meta(name: &str, meta: &ProfileMeta) -> Result<()> {
+    ensure_profiles_dir()?;
+    let path = profile_meta_path(name);
+    fs::write(&path, serde_json::to_string_pretty(meta)?)
+        .with_context(|| format!("writing profile metadata {}", path.display()))
+}
+
+/// Start the metadata of a newly added or imported profile.
+/// `ccm add --force` keeps the existing metadata, creation time and switches included.
+pub fn record_created(name: &str, provider: Option<&str>) -> Result<()> {
+    let mut meta = load_meta(name).unwrap_or_default();
+    meta.created_at.get_or_insert_with(now);
+    if let Some(provider) = provider {
+        meta.provider = Some(provider.to_string());
+    }
+    save_meta(name, &meta)
+}
+
+/// Count a switch to the profile. Failing to record it only warns, since
+/// the switch itself has already happened.
+pub fn record_switch(name: &str) {
+    let result = load_meta(name).and_then(|mut meta| {
+        meta.last_switched_at = Some(now());
+        meta.switch_count += 1;
+        save_meta(name, &meta)
+    });
+    if let Err(e) = result {
+        eprintln!(
+            "⚠️  Failed to update metadata of profile '{}': {:#}",
+            name, e
+        );
+    }
+}
+
+/// Forget a profile's metadata
+pub fn remove_meta(name: &str) -> Result<()> {
+    let path = profile_meta_path(name);
+    if path.exists() {
+        fs::remove_file(&path)
+            .with_context(|| format!("removing profile metadata {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Move a profile's metadata along with a rename
+pub fn rename_meta(origin: &str, new: &str) -> Result<()> {
+    let origin_path = profile_meta_path(origin);
+    if origin_path.exists() {
+        fs::rename(&origin_path, profile_meta_path(new))
+            .with_context(|| format!("renaming profile metadata {}", origin_path.display()))?;
+    }
+    Ok(())
+}
+
+/// A profile's metadata with its name, as printed by `ccm describe --output json`
+#[derive(Serialize)]
+struct Described<'a> {
+    name: &'a str,
+    #[serde(flatten)]
+    meta: &'a ProfileMeta,
+}
+
+/// Print a profile's metadata, after applying any changes given
+pub fn describe_profile(
+    name: &str,
+    description: Option<&str>,
+    tags: &[String],
+    untags: &[String],
+    provider: Option<&str>,
+    format: OutputFormat,
+) -> Result<()> {
+    if !profile_path(name).exists() {
+        bail!("Profile '{}' does not exist", name);
+    }
+    let original = load_meta(name)?;
+    let mut meta = original.clone();
+    let non_empty = |s: &str| Some(s.trim().to_string()).filter(|s| !s.is_empty());
+    if let Some(description) = description {
+        meta.description = non_empty(description);
+    }
+    if let Some(provider) = provider {
+        meta.provider = non_empty(provider);
+    }
+    for tag in tags.iter().filter_map(|t| non_empty(t)) {
+        if !meta.tags.contains(&tag) {
+            meta.tags.push(tag);
+        }
+    }
+    meta.tags.retain(|t| !untags.contains(t));
+    let changed = meta != original;
+    if changed {
+        save_meta(name, &meta)?;
+    }
+
+    if !format.is_text() {
+        return emit(format, &Described { name, meta: &meta });
+    }
+    if changed {
+        say!("✓ Updated profile '{}'", name);
+    } else {
+        say!("Profile '{}'", name);
+    }
+    let or_dash = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+    say!("  Description:    {}", or_dash(&meta.description));
+    say!(
+        "  Tags:           {}",
+        if meta.tags.is_empty() {
+            "-".to_string()
+        } else {
+            meta.tags.join(", ")
+        }
+    );
+    say!("  Provider:       {}", or_dash(&meta.provider));
+    say!(
+        "  Created:        {}",
+        or_dash(&meta.created_at.as_deref().map(display_time))
+    );
+    match &meta.last_switched_at {
+        Some(at) => say!(
+            "  Last switched:  {} ({})",
+            display_time(at),
+            switches(meta.switch_count)
+        ),
+        None => say!("  Last switched:  never"),
+    }
+    Ok(())
+}
@@ -0,0 +1,528 @@
+use anyhow::{Context, Result, anyhow, bail};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::fs;
+
+use crate::config::{ensure_profiles_dir, profile_path};
+
+/// Env key for the Anthropic-compatible endpoint
+pub const BASE_URL: &str = "ANTHROPIC_BASE_URL";
+/// Env key for the auth token sent as a bearer token
+pub const AUTH_TOKEN: &str = "ANTHROPIC_AUTH_TOKEN";
+/// Env key for the API key sent as `x-api-key`
+pub const API_KEY: &str = "ANTHROPIC_API_KEY";
+/// Env key for the main model
+pub const MODEL: &str = "ANTHROPIC_MODEL";
+/// Env key for the small/fast (background) model
+pub const SMALL_FAST_MODEL: &str = "ANTHROPIC_SMALL_FAST_MODEL";
+/// Env key for the request timeout in milliseconds
+pub const API_TIMEOUT_MS: &str = "API_TIMEOUT_MS";
+/// Env key that disables telemetry and other nonessential traffic
+pub const DISABLE_NONESSENTIAL_TRAFFIC: &str = "CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC";
+
+/// Integer env value that remembers whether it was written as a JSON string,
+/// so that loading and saving a profile does not change its representation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntSetting {
+    pub value: i64,
+    pub quoted: bool,
+}
+
+impl IntSetting {
+    /// `None` for anything but an integer or a string holding one
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Number(n) => n.as_i64().map(|value| Self {
+                value,
+                quoted: false,
+            }),
+            Value::String(s) => s.trim().parse::<i64>().ok().map(|value| Self {
+                value,
+                quoted: true,
+            }),
+            _ => None,
+        }
+    }
+
+    fn to_value(self) -> Value {
+        if self.quoted {
+            Value::String(self.value.to_string())
+        } else {
+            Value::Number(self.value.into())
+        }
+    }
+}
+
+impl fmt::Display for IntSetting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+/// Key marking an env value object as a command whose output is the value
+pub const CMD_KEY: &str = "$cmd";
+/// Optional per-command timeout in milliseconds
+pub const CMD_TIMEOUT_KEY: &str = "$timeout_ms";
+
+/// A command that prints a secret, written as `{"$cmd": "pass show llm/kimi"}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandSource {
+    pub cmd: String,
+    pub timeout_ms: Option<u64>,
+}
+
+impl CommandSource {
+    /// Parse a `{"$cmd": ...}` object. Returns `Ok(None)` for any other value.
+    pub fn from_value(key: &str, value: &Value) -> Result<Option<Self>> {
+        let Some(map) = value.as_object().filter(|m| m.contains_key(CMD_KEY)) else {
+            return Ok(None);
+        };
+        let cmd = match &map[CMD_KEY] {
+            Value::String(cmd) if !cmd.trim().is_empty() => cmd.clone(),
+            other => bail!(
+                "env.{}: {} must be a non-empty string, got {}",
+                key,
+                CMD_KEY,
+                other
+            ),
+        };
+        let timeout_ms = match map.get(CMD_TIMEOUT_KEY) {
+            None => None,
+            Some(v) => Some(v.as_u64().ok_or_else(|| {
+                anyhow!(
+                    "env.{}: {} must be a positive integer",
+                    key,
+                    CMD_TIMEOUT_KEY
+                )
+            })?),
+        };
+        if let Some(unknown) = map.keys().find(|k| *k != CMD_KEY && *k != CMD_TIMEOUT_KEY) {
+            bail!(
+                "env.{}: unknown field '{}' in command reference",
+                key,
+                unknown
+            );
+        }
+        Ok(Some(Self { cmd, timeout_ms }))
+    }
+
+    pub fn to_value(&self) -> Value {
+        let mut map = Map::new();
+        map.insert(CMD_KEY.to_string(), Value::String(self.cmd.clone()));
+        if let Some(timeout) = self.timeout_ms {
+            map.insert(CMD_TIMEOUT_KEY.to_string(), Value::Number(timeout.into()));
+        }
+        Value::Object(map)
+    }
+}
+
+/// Env value that may be a secret: literal text or a command that prints it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretSource {
+    Text(String),
+    Command(CommandSource),
+}
+
+impl SecretSource {
+    /// `None` for anything but a string or a valid command reference
+    fn from_value(key: &str, value: &Value) -> Option<Self> {
+        match CommandSource::from_value(key, value) {
+            Ok(Some(cmd)) => Some(Self::Command(cmd)),
+            Ok(None) => value.as_str().map(|s| Self::Text(s.to_string())),
+            Err(_) => None,
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        match self {
+            Self::Text(text) => Value::String(text.clone()),
+            Self::Command(cmd) => cmd.to_value(),
+        }
+    }
+}
+
+/// The `env` block of a profile: well-known keys are typed, everything else
+/// (including well-known keys with values of another type) is kept as-is in
+/// `extra`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProfileEnv {
+    pub base_url: Option<String>,
+    pub auth_token: Option<SecretSource>,
+    pub model: Option<String>,
+    pub small_fast_model: Option<String>,
+    pub api_timeout_ms: Option<IntSetting>,
+    pub disable_nonessential_traffic: Option<IntSetting>,
+    pub extra: Map<String, Value>,
+}
+
+impl ProfileEnv {
+    pub fn is_empty(&self) -> bool {
+        self.to_map().is_empty()
+    }
+
+    /// Set an env key, routing well-known keys to their typed field. Values the
+    /// field cannot hold are kept as-is, since Claude Code may still accept
+    /// them; `ccm validate` reports them.
+    pub fn set(&mut self, key: &str, value: Value) {
+        self.extra.remove(key);
+        let text = || value.as_str().map(str::to_string);
+        let typed = match key {
+            BASE_URL => {
+                self.base_url = text().filter(|url| !url.trim().is_empty());
+                self.base_url.is_some()
+            }
+            AUTH_TOKEN => {
+                self.auth_token = SecretSource::from_value(key, &value);
+                self.auth_token.is_some()
+            }
+            MODEL => {
+                self.model = text();
+                self.model.is_some()
+            }
+            SMALL_FAST_MODEL => {
+                self.small_fast_model = text();
+                self.small_fast_model.is_some()
+            }
+            // Placeholders never parse, so they are kept until expanded
+            API_TIMEOUT_MS => {
+                self.api_timeout_ms = IntSetting::from_value(&value);
+                self.api_timeout_ms.is_some()
+            }
+            DISABLE_NONESSENTIAL_TRAFFIC => {
+                self.disable_nonessential_traffic = IntSetting::from_value(&value);
+                self.disable_nonessential_traffic.is_some()
+            }
+            _ => false,
+        };
+        if !typed {
+            self.extra.insert(key.to_string(), value);
+        }
+    }
+
+    /// Build the env block from a JSON object
+    pub fn from_map(map: Map<String, Value>) -> Self {
+        let mut env = Self::default();
+        for (key, value) in map {
+            env.set(&key, value);
+        }
+        env
+    }
+
+    /// Env values as process environment variables. Non-string values are
+    /// written as JSON text; nulls are skipped.
+    pub fn to_env_vars(&self) -> Vec<(String, String)> {
+        self.to_map()
+            .into_iter()
+            .filter_map(|(key, value)| match value {
+                Value::Null => None,
+                Value::String(s) => Some((key, s)),
+                other => Some((key, other.to_string())),
+            })
+            .collect()
+    }
+
+    /// Convert back into a JSON object
+    pub fn to_map(&self) -> Map<String, Value> {
+        let mut map = self.extra.clone();
+        if let Some(token) = &self.auth_token {
+            map.insert(AUTH_TOKEN.to_string(), token.to_value());
+        }
+        let strings = [
+            (BASE_URL, &self.base_url),
+            (MODEL, &self.model),
+            (SMALL_FAST_MODEL, &self.small_fast_model),
+        ];
+        for (key, value) in strings {
+            if let Some(v) = value {
+                map.insert(key.to_string(), Value::String(v.clone()));
+            }
+        }
+        let ints = [
+            (API_TIMEOUT_MS, self.api_timeout_ms),
+            (
+                DISABLE_NONESSENTIAL_TRAFFIC,
+                self.disable_nonessential_traffic,
+            ),
+        ];
+        for (key, value) in ints {
+            if let Some(v) = value {
+                map.insert(key.to_string(), v.to_value());
+            }
+        }
+        map
+    }
+}
+
+/// Merge profile into existing settings (profile fields override existing ones)
+pub fn merge_json(base: &mut Value, overlay: &Value) {
+    match (base, overlay) {
+        (Value::Object(base_map), Value::Object(overlay_map)) => {
+            for (key, overlay_value) in overlay_map {
+                match base_map.get_mut(key) {
+                    Some(base_value) => {
+                        // Recursively merge objects
+                        merge_json(base_value, overlay_value);
+                    }
+                    None => {
+                        // Key doesn't exist in base, insert it
+                        base_map.insert(key.clone(), overlay_value.clone());
+                    }
+                }
+            }
+        }
+        (base, overlay) => {
+            // For non-object values, overlay replaces base
+            *base = overlay.clone();
+        }
+    }
+}
+
+/// Remove entries from `value` that are identical in `inherited` (recursive),
+/// leaving only what a child profile adds or overrides
+fn strip_inherited(value: &mut Value, inherited: &Value) {
+    if let (Value::Object(map), Value::Object(inherited_map)) = (value, inherited) {
+        map.retain(|key, v| match inherited_map.get(key) {
+            Some(Value::Object(_)) if v.is_object() => {
+                strip_inherited(v, &inherited_map[key]);
+                v.as_object().is_some_and(|m| !m.is_empty())
+            }
+            Some(inherited_value) => v != inherited_value,
+            None => true,
+        });
+    }
+}
+
+/// Profile key choosing how a global switch writes `~/.claude/settings.json`
+pub const SWITCH_MODE_KEY: &str = "switchMode";
+
+/// How a global switch applies a profile
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum SwitchMode {
+    /// Overwrite settings.json with the profile
+    #[default]
+    Replace,
+    /// Replace only the keys the profile (and the previous profile) defines
+    Merge,
+}
+
+impl SwitchMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Replace => "replace",
+            Self::Merge => "merge",
+        }
+    }
+
+    pub fn from_value(value: Value) -> Result<Self> {
+        match value.as_str() {
+            Some("replace") => Ok(Self::Replace),
+            Some("merge") => Ok(Self::Merge),
+            _ => bail!(
+                "{}: expected \"replace\" or \"merge\", got {}",
+                SWITCH_MODE_KEY,
+                value
+            ),
+        }
+    }
+}
+
+/// Which project settings file a project switch writes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ProjectTarget {
+    /// `.claude/settings.json`, usually committed and shared with the team;
+    /// secrets still go to `settings.local.json`
+    Shared,
+    /// `.claude/settings.local.json`, personal and not committed
+    #[default]
+    Local,
+}
+
+impl ProjectTarget {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Shared => "shared",
+            Self::Local => "local",
+        }
+    }
+}
+
+/// A validated Claude settings profile
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Value", into = "Value")]
+pub struct Profile {
+    /// Parent profiles this one inherits from, applied in order
+    pub extends: Vec<String>,
+    /// How a global switch applies this profile; unset means replace
+    pub switch_mode: Option<SwitchMode>,
+    pub env: ProfileEnv,
+    /// Top-level settings other than `env` (permissions, hooks, model, ...)
+    pub settings: Map<String, Value>,
+}
+
+impl TryFrom<Value> for Profile {
+    type Error = anyhow::Error;
+
+    fn try_from(value: Value) -> Result<Self> {
+        let Value::Object(mut settings) = value else {
+            bail!("profile must be a JSON object");
+        };
+        let extends = match settings.remove("extends") {
+            None => Vec::new(),
+            Some(Value::String(parent)) => vec![parent],
+            Some(Value::Array(parents)) => parents
+                .into_iter()
+                .map(|p| match p {
+                    Value::String(s) => Ok(s),
+                    other => bail!("extends: expected profile names, got {}", other),
+                })
+                .collect::<Result<_>>()?,
+            Some(other) => bail!("extends: expected a profile name or a list, got {}", other),
+        };
+        let switch_mode = settings
+            .remove(SWITCH_MODE_KEY)
+            .map(SwitchMode::from_value)
+            .transpose()?;
+        let env = match settings.remove("env") {
+            None => ProfileEnv::default(),
+            Some(Value::Object(map)) => ProfileEnv::from_map(map),
+            Some(other) => bail!("env: expected an object, got {}", other),
+        };
+        Ok(Self {
+            extends,
+            switch_mode,
+            env,
+            settings,
+        })
+    }
+}
+
+impl From<Profile> for Value {
+    fn from(profile: Profile) -> Self {
+        profile.to_value()
+    }
+}
+
+impl Profile {
+    /// Parse and validate a profile from JSON text
+    pub fn from_json_str(content: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(content)?;
+        Self::try_from(value)
+    }
+
+    /// Load and validate a saved profile by name
+    pub fn load(name: &str) -> Result<Self> {
+        let p = profile_path(name);
+        let content =
+            fs::read_to_string(&p).with_context(|| format!("reading profile {}", p.display()))?;
+        Self::from_json_str(&content)
+            .with_context(|| format!("parsing profile JSON from {}", p.display()))
+    }
+
+    /// Load a profile and flatten its `extends` chain into a single profile.
+    /// Parents are merged in order with `merge_json`, then the profile itself on top.
+    pub fn load_resolved(name: &str) -> Result<Self> {
+        let value = resolve_chain(name, &mut Vec::new())?;
+        Self::try_from(value).with_context(|| format!("resolving profile '{}'", name))
+    }
+
+    /// Turn a flattened settings value back into the profile stored as `name`,
+    /// keeping its `extends` and `switchMode` and dropping whatever the parents
+    /// already provide
+    pub fn rebase(name: &str, mut resolved: Value) -> Result<Self> {
+        let stored = Self::load(name)?;
+        if !stored.extends.is_empty() {
+            let inherited = resolve_parents(&stored.extends, &mut vec![name.to_string()])?;
+            strip_inherited(&mut resolved, &inherited);
+        }
+        let mut profile = Self::try_from(resolved)?;
+        profile.extends = stored.extends;
+        profile.switch_mode = stored.switch_mode;
+        Ok(profile)
+    }
+
+    /// Save the profile under the given name, overwriting any existing file
+    pub fn save(&self, name: &str) -> Result<()> {
+        ensure_profiles_dir()?;
+        let p = profile_path(name);
+        fs::write(&p, self.to_json_pretty()?)
+            .with_context(|| format!("writing profile {}", p.display()))
+    }
+
+    pub fn to_value(&self) -> Value {
+        let mut map = self.settings.clone();
+        match self.extends.as_slice() {
+            [] => {}
+            [parent] => {
+                map.insert("extends".to_string(), Value::String(parent.clone()));
+            }
+            parents => {
+                map.insert("extends".to_string(), serde_json::json!(parents));
+            }
+        }
+        if let Some(mode) = self.switch_mode {
+            map.insert(
+                SWITCH_MODE_KEY.to_string(),
+                Value::String(mode.as_str().to_string()),
+            );
+        }
+        if !self.env.is_empty() {
+            map.insert("env".to_string(), Value::Object(self.env.to_map()));
+        }
+        Value::Object(map)
+    }
+
+    /// The settings Claude sees: the profile without ccm's own keys
+    pub fn to_settings_value(&self) -> Value {
+        let mut value = self.to_value();
+        if let Value::Object(map) = &mut value {
+            map.remove("extends");
+            map.remove(SWITCH_MODE_KEY);
+        }
+        value
+    }
+
+    pub fn to_json_pretty(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&self.to_value())?)
+    }
+}
+
+/// Resolve `name` to a flattened JSON value, tracking the chain to detect cycles
+fn resolve_chain(name: &str, chain: &mut Vec<String>) -> Result<Value> {
+    if chain.iter().any(|n| n == name) {
+        chain.push(name.to_string());
+        bail!("Profile inheritance cycle: {}", chain.join(" -> "));
+    }
+    if !profile_path(name).exists() {
+        match chain.last() {
+            Some(child) => bail!(
+                "Profile '{}' extends '{}', which does not exist",
+                child,
+                name
+            ),
+            None => bail!("Profile '{}' does not exist", name),
+        }
+    }
+
+    let profile = Profile::load(name)?;
+    chain.push(name.to_string());
+    let mut resolved = resolve_parents(&profile.extends, chain)?;
+    let mut own = profile.to_value();
+    if let Value::Object(map) = &mut own {
+        map.remove("extends");
+    }
+    merge_json(&mut resolved, &own);
+    chain.pop();
+    Ok(resolved)
+}
+
+/// Merge the resolved parents in order into one value
+fn resolve_parents(parents: &[String], chain: &mut Vec<String>) -> Result<Value> {
+    let mut resolved = Value::Object(Map::new());
+    for parent in parents {
+        let parent_value = resolve_chain(parent, chain)?;
+        merge_json(&mut resolved, &parent_value);
+    }
+    Ok(resolved)
+}
@@ -0,0 +1,112 @@
+use anyhow::{Error, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::json;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::redact::redact_text;
+
+/// How command results are printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    Json,
+    Yaml,
+}
+
+impl OutputFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Json => "json",
+            Self::Yaml => "yaml",
+        }
+    }
+
+    pub fn is_text(self) -> bool {
+        self == Self::Text
+    }
+}
+
+/// Set when results are printed as JSON or YAML, so that messages meant for
+/// people go to stderr and stdout holds only the result
+static MESSAGES_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Choose where messages go for the rest of the run
+pub fn set_format(format: OutputFormat) {
+    MESSAGES_TO_STDERR.store(!format.is_text(), Ordering::Relaxed);
+}
+
+pub fn messages_to_stderr() -> bool {
+    MESSAGES_TO_STDERR.load(Ordering::Relaxed)
+}
+
+/// Print a message for people: to stdout, or to stderr when printing
+/// structured results
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if $crate::output::messages_to_stderr() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
+/// Print a prompt without a newline, where messages go
+pub fn prompt(text: &str) -> Result<()> {
+    if messages_to_stderr() {
+        eprint!("{}", text);
+        io::stderr().flush()?;
+    } else {
+        print!("{}", text);
+        io::stdout().flush()?;
+    }
+    Ok(())
+}
+
+/// Print a command result as JSON or YAML
+pub fn emit<T: Serialize>(format: OutputFormat, value: &T) -> Result<()> {
+    match format {
+        OutputFormat::Yaml => print!("{}", serde_yaml::to_string(value)?),
+        _ => println!("{}", serde_json::to_string_pretty(value)?),
+    }
+    Ok(())
+}
+
+/// A failure the emitted result already reports: the command exits with
+/// code 1 without printing an error after it
+#[derive(Debug)]
+pub struct Reported;
+
+impl fmt::Display for Reported {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("the result reports a failure")
+    }
+}
+
+impl std::error::Error for Reported {}
+
+/// Print an error in the stable machine-readable shape:
+/// `{"error": {"message": "...", "causes": ["..."]}}`, with credentials masked
+pub fn emit_error(format: OutputFormat, error: &Error) -> Result<()> {
+    let causes: Vec<String> = error
+        .chain()
+        .skip(1)
+        .map(|c| redact_text(&c.to_string()))
+        .collect();
+    emit(
+        format,
+        &json!({
+            "error": {
+                "message": redact_text(&error.to_string()),
+                "causes": causes,
+            }
+        }),
+    )
+}
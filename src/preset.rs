@@ -0,0 +1,151 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::config::presets_path;
+use crate::model::{BASE_URL, IntSetting, ProfileEnv};
+use crate::output::{OutputFormat, emit};
+use crate::say;
+
+/// Provider defaults for a new profile; everything except the token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Preset {
+    #[serde(default)]
+    pub description: String,
+    pub base_url: String,
+    pub model: Option<String>,
+    pub small_fast_model: Option<String>,
+    pub timeout_ms: Option<u64>,
+    /// Extra env entries, e.g. CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC
+    #[serde(default)]
+    pub env: Map<String, Value>,
+    /// Set for presets loaded from the user's presets file
+    #[serde(skip_deserializing)]
+    pub custom: bool,
+}
+
+impl Preset {
+    /// The env block a profile created from this preset starts with
+    pub fn to_env(&self) -> Result<ProfileEnv> {
+        let mut env = ProfileEnv::from_map(self.env.clone());
+        env.set(BASE_URL, Value::String(self.base_url.clone()));
+        env.model = self.model.clone();
+        env.small_fast_model = self.small_fast_model.clone();
+        if let Some(timeout) = self.timeout_ms {
+            env.api_timeout_ms = Some(IntSetting {
+                value: i64::try_from(timeout)?,
+                quoted: true,
+            });
+        }
+        Ok(env)
+    }
+}
+
+/// Presets shipped with ccm, matching the providers listed in the README
+fn builtin_presets() -> Value {
+    json!({
+        "deepseek": {
+            "description": "DeepSeek",
+            "base_url": "https://api.deepseek.com/anthropic",
+            "model": "deepseek-chat",
+            "small_fast_model": "deepseek-chat",
+            "timeout_ms": 600000,
+            "env": { "CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC": 1 }
+        },
+        "kimi": {
+            "description": "Moonshot Kimi K2 (platform.moonshot.ai)",
+            "base_url": "https://api.moonshot.ai/anthropic",
+            "model": "kimi-k2-0905-preview",
+            "small_fast_model": "kimi-k2-0905-preview"
+        },
+        "kimi-cn": {
+            "description": "Moonshot Kimi K2 (platform.moonshot.cn)",
+            "base_url": "https://api.moonshot.cn/anthropic",
+            "model": "kimi-k2-0905-preview",
+            "small_fast_model": "kimi-k2-0905-preview"
+        },
+        "glm": {
+            "description": "Zhipu GLM (z.ai)",
+            "base_url": "https://api.z.ai/api/anthropic",
+            "model": "glm-4.6",
+            "small_fast_model": "glm-4.5-air",
+            "timeout_ms": 3000000
+        },
+        "glm-cn": {
+            "description": "Zhipu GLM (bigmodel.cn)",
+            "base_url": "https://open.bigmodel.cn/api/anthropic",
+            "model": "glm-4.6",
+            "small_fast_model": "glm-4.5-air",
+            "timeout_ms": 3000000
+        },
+        "minimax": {
+            "description": "MiniMax M2 (minimax.io)",
+            "base_url": "https://api.minimax.io/anthropic",
+            "model": "MiniMax-M2",
+            "small_fast_model": "MiniMax-M2",
+            "timeout_ms": 3000000,
+            "env": { "CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC": 1 }
+        },
+        "minimax-cn": {
+            "description": "MiniMax M2 (minimaxi.com)",
+            "base_url": "https://api.minimaxi.com/anthropic",
+            "model": "MiniMax-M2",
+            "small_fast_model": "MiniMax-M2",
+            "timeout_ms": 3000000,
+            "env": { "CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC": 1 }
+        }
+    })
+}
+
+/// All presets by name; user presets override built-in ones of the same name
+pub fn load_presets() -> Result<BTreeMap<String, Preset>> {
+    let mut presets: BTreeMap<String, Preset> = serde_json::from_value(builtin_presets())?;
+
+    let path = presets_path();
+    if path.exists() {
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("reading presets from {}", path.display()))?;
+        let custom: BTreeMap<String, Preset> = serde_json::from_str(&content)
+            .with_context(|| format!("parsing presets from {}", path.display()))?;
+        for (name, mut preset) in custom {
+            preset.custom = true;
+            presets.insert(name, preset);
+        }
+    }
+    Ok(presets)
+}
+
+/// Look up a preset by name
+pub fn find_preset(name: &str) -> Result<Preset> {
+    load_presets()?.remove(name).ok_or_else(|| {
+        anyhow!(
+            "Unknown preset '{}'. Run `ccm presets` to see the available ones.",
+            name
+        )
+    })
+}
+
+/// Print every available preset
+pub fn list_presets(format: OutputFormat) -> Result<()> {
+    if !format.is_text() {
+        return emit(format, &load_presets()?);
+    }
+    say!("Presets (add your own in {}):", presets_path().display());
+    for (name, preset) in load_presets()? {
+        let custom = if preset.custom { " (custom)" } else { "" };
+        say!(" - {}{}: {}", name, custom, preset.description);
+        say!("     {}", preset.base_url);
+        if let Some(model) = &preset.model {
+            match &preset.small_fast_model {
+                Some(small) if small != model => {
+                    say!("     model: {}, small/fast: {}", model, small)
+                }
+                _ => say!("     model: {}", model),
+            }
+        }
+    }
+    Ok(())
+}
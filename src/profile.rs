@@ -1,48 +1,61 @@
 use anyhow::{Context, Result};
 use rpassword::read_password;
-use serde_json::Value;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, Read};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
+use crate::cli::{AddArgs, ListArgs};
 use crate::config::{
-    claude_settings_path, current_profile_path, ensure_ccm_dir, ensure_profiles_dir,
-    get_current_working_dir, get_project_profile_info, profile_path, remove_project_profile,
-    set_project_profile,
+    ManagedKeys, ProjectMapping, claude_settings_path, current_profile_path, current_project_root,
+    current_switch_mode_path, ensure_ccm_dir, ensure_profiles_dir, find_project_mapping,
+    get_current_working_dir, get_project_mapping, get_project_profile_info, profile_path,
+    remove_project_profile, set_project_profile,
 };
+use crate::diff::{diff, format_value, print_diff};
+use crate::meta::{
+    ProfileMeta, ProfileSort, display_time, load_meta, record_created, record_switch, remove_meta,
+    rename_meta, switches,
+};
+use crate::model::{
+    API_KEY, API_TIMEOUT_MS, BASE_URL, DISABLE_NONESSENTIAL_TRAFFIC, IntSetting, Profile,
+    ProfileEnv, ProjectTarget, SecretSource, SwitchMode, merge_json,
+};
+use crate::output::{self, OutputFormat, emit};
+use crate::preset::find_preset;
+use crate::redact::{has_credentials, redact_settings};
+use crate::say;
+use crate::secret::{
+    has_secret_refs, needs_resolving, resolve_secrets, resolve_secrets_masked, restore_refs,
+};
+use crate::snapshot::{
+    KeyPath, ThreeWay, flatten, load_snapshot, path_string, remove_snapshot, rename_snapshot,
+    save_snapshot, unflatten,
+};
+use crate::validate::{check_issues, check_profile, profile_issues, validate_profile};
 
-/// Display a simple JSON diff by showing both values side by side
+/// Show how settings.json drifted from the current profile, key by key
 fn display_json_diff(profile_name: &str, profile_value: &Value, settings_value: &Value) {
-    println!("\n⚠️  Configuration mismatch detected!");
-    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-    println!(
-        "Current profile '{}' differs from settings.json\n",
+    say!("\n⚠️  Configuration mismatch detected!");
+    say!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    say!(
+        "Current profile '{}' differs from settings.json:\n",
         profile_name
     );
-
-    println!("Profile '{}' content:", profile_name);
-    println!(
-        "{}",
-        serde_json::to_string_pretty(profile_value).unwrap_or_default()
-    );
-
-    println!("\nsettings.json content:");
-    println!(
-        "{}",
-        serde_json::to_string_pretty(settings_value).unwrap_or_default()
-    );
-    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+    print_diff(&diff(profile_value, settings_value));
+    say!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
 }
 
-/// Prompt user to choose what to do when profile differs from settings
-fn prompt_switch_action() -> Result<u32> {
-    println!("What would you like to do?");
-    println!("  1: Switch directly (ignore the difference)");
-    println!("  2: Update current profile with settings.json, then switch");
-    println!("  3: Cancel switch operation");
-    print!("\nYour choice [1-3]: ");
-    io::stdout().flush()?;
+/// Prompt user to choose what to do when profile differs from `source`
+fn prompt_switch_action(source: &str) -> Result<u32> {
+    say!("What would you like to do?");
+    say!("  1: Switch directly (ignore the difference)");
+    say!("  2: Update current profile with {}, then switch", source);
+    say!("  3: Cancel switch operation");
+    output::prompt("\nYour choice [1-3]: ")?;
 
     let mut input = String::new();
     io::stdin().read_line(&mut input)?;
@@ -50,7 +63,7 @@ fn prompt_switch_action() -> Result<u32> {
     match input.trim().parse::<u32>() {
         Ok(n) if (1..=3).contains(&n) => Ok(n),
         _ => {
-            println!("Invalid choice. Operation cancelled.");
+            say!("Invalid choice. Operation cancelled.");
             Ok(3)
         }
     }
@@ -58,8 +71,7 @@ fn prompt_switch_action() -> Result<u32> {
 
 /// Prompt user for input
 fn prompt_input(prompt: &str) -> Result<String> {
-    print!("{}", prompt);
-    io::stdout().flush()?;
+    output::prompt(prompt)?;
     let mut input = String::new();
     io::stdin().read_line(&mut input)?;
     Ok(input.trim().to_string())
@@ -67,112 +79,217 @@ fn prompt_input(prompt: &str) -> Result<String> {
 
 /// Prompt user for password input (hidden input)
 fn prompt_password(prompt: &str) -> Result<String> {
-    print!("{}", prompt);
-    io::stdout().flush()?;
+    output::prompt(prompt)?;
     let password = read_password()?;
     Ok(password.trim().to_string())
 }
 
-/// Add a profile interactively
-pub fn add_profile_interactive(name: &str, env_vars: &[String]) -> Result<()> {
-    println!(
-        "Adding profile '{}' - please answer the following questions:",
-        name
-    );
+/// Endpoint suggested when adding a profile
+const DEFAULT_BASE_URL: &str = "https://api.anthropic.com";
+
+/// Prompt for the base URL, using the default on an empty answer
+fn prompt_base_url() -> Result<String> {
+    let answer = prompt_input(&format!("ANTHROPIC_BASE_URL [{}]: ", DEFAULT_BASE_URL))?;
+    Ok(non_empty(answer).unwrap_or_else(|| DEFAULT_BASE_URL.to_string()))
+}
 
-    let base_url = prompt_input("ANTHROPIC_BASE_URL: ")?.trim().to_string();
+/// Prompt for a token until one is given, so a stray Enter doesn't throw
+/// away the answers so far
+fn prompt_token() -> Result<String> {
+    for _ in 0..3 {
+        let token = prompt_password("ANTHROPIC_AUTH_TOKEN: ")?;
+        if !token.is_empty() {
+            return Ok(token);
+        }
+        say!("A token is required (or use --token-stdin or --token-env).");
+    }
+    anyhow::bail!("ANTHROPIC_AUTH_TOKEN is required")
+}
+
+/// Parse an optional integer answer, leaving it unset when empty
+fn parse_int_answer(key: &str, answer: String) -> Result<Option<IntSetting>> {
+    if answer.is_empty() {
+        return Ok(None);
+    }
+    let value = answer
+        .parse::<i64>()
+        .map_err(|_| anyhow::anyhow!("Invalid integer for {}: '{}'", key, answer))?;
+    Ok(Some(IntSetting {
+        value,
+        quoted: key == API_TIMEOUT_MS,
+    }))
+}
+
+/// Turn an empty answer into `None`
+fn non_empty(answer: String) -> Option<String> {
+    if answer.is_empty() {
+        None
+    } else {
+        Some(answer)
+    }
+}
+
+/// Ask every profile question, as `ccm add` does without input flags
+fn prompt_full_env() -> Result<ProfileEnv> {
+    let base_url = prompt_base_url()?;
     let auth_token = prompt_password("ANTHROPIC_AUTH_TOKEN: ")?;
-    let model = prompt_input("ANTHROPIC_MODEL (optional, press Enter to skip): ")?
-        .trim()
-        .to_string();
+    let model = prompt_input("ANTHROPIC_MODEL (optional, press Enter to skip): ")?;
     let small_fast_model =
-        prompt_input("ANTHROPIC_SMALL_FAST_MODEL (optional, press Enter to skip): ")?
-            .trim()
-            .to_string();
-    let timeout = prompt_input("API_TIMEOUT_MS (optional, press Enter to skip): ")?
-        .trim()
-        .to_string();
+        prompt_input("ANTHROPIC_SMALL_FAST_MODEL (optional, press Enter to skip): ")?;
+    let timeout = prompt_input("API_TIMEOUT_MS (optional, press Enter to skip): ")?;
     let disable_nonessential = prompt_input(
         "CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC (optional int, e.g., 1; press Enter to skip): ",
-    )?
-    .trim()
-    .to_string();
+    )?;
 
-    let mut obj = serde_json::Map::new();
-    let mut env_obj = serde_json::Map::new();
+    Ok(ProfileEnv {
+        base_url: Some(base_url),
+        auth_token: non_empty(auth_token).map(SecretSource::Text),
+        model: non_empty(model),
+        small_fast_model: non_empty(small_fast_model),
+        api_timeout_ms: parse_int_answer(API_TIMEOUT_MS, timeout)?,
+        disable_nonessential_traffic: parse_int_answer(
+            DISABLE_NONESSENTIAL_TRAFFIC,
+            disable_nonessential,
+        )?,
+        ..Default::default()
+    })
+}
 
-    // Add required env variables
-    if !base_url.is_empty() {
-        env_obj.insert("ANTHROPIC_BASE_URL".to_string(), Value::String(base_url));
+/// Read a profile from a JSON file, or from stdin when `source` is `-`
+fn read_profile_json(source: &str) -> Result<Profile> {
+    let content = if source == "-" {
+        let mut input = String::new();
+        io::stdin()
+            .read_to_string(&mut input)
+            .context("reading profile JSON from stdin")?;
+        input
+    } else {
+        fs::read_to_string(source).with_context(|| format!("reading {}", source))?
+    };
+    Profile::from_json_str(&content)
+        .with_context(|| format!("parsing profile JSON from {}", source))
+}
+
+/// Get the auth token from --token-stdin or --token-env, if either was given
+fn token_from_args(args: &AddArgs) -> Result<Option<String>> {
+    let token = if args.token_stdin {
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        input.trim().to_string()
+    } else if let Some(var) = &args.token_env {
+        std::env::var(var)
+            .with_context(|| format!("reading token from environment variable {}", var))?
+            .trim()
+            .to_string()
+    } else {
+        return Ok(None);
+    };
+    if token.is_empty() {
+        anyhow::bail!("ANTHROPIC_AUTH_TOKEN must not be empty");
     }
-    if !auth_token.is_empty() {
-        env_obj.insert(
-            "ANTHROPIC_AUTH_TOKEN".to_string(),
-            Value::String(auth_token),
+    Ok(Some(token))
+}
+
+/// Add a profile from flags, a JSON file and/or interactive prompts.
+/// Without any input flags every question is asked; otherwise only missing
+/// required fields are prompted for (or rejected with `--no-input`).
+pub fn add_profile(name: &str, args: &AddArgs) -> Result<()> {
+    if profile_path(name).exists() && !args.force {
+        anyhow::bail!(
+            "Profile '{}' already exists. Use --force to overwrite it.",
+            name
         );
     }
+    if args.token_stdin && args.from_json.as_deref() == Some("-") {
+        anyhow::bail!("--token-stdin and --from-json - cannot both read from stdin");
+    }
+
+    let has_input = args.preset.is_some()
+        || args.base_url.is_some()
+        || args.token_stdin
+        || args.token_env.is_some()
+        || args.model.is_some()
+        || args.small_fast_model.is_some()
+        || args.timeout_ms.is_some()
+        || args.from_json.is_some();
+
+    let mut profile = match (&args.preset, &args.from_json) {
+        (Some(preset), _) => Profile {
+            env: find_preset(preset)?.to_env()?,
+            ..Default::default()
+        },
+        (None, Some(source)) => read_profile_json(source)?,
+        (None, None) => Profile::default(),
+    };
 
-    // Add optional env variables
-    if !model.is_empty() {
-        env_obj.insert("ANTHROPIC_MODEL".to_string(), Value::String(model));
+    if !has_input && !args.no_input {
+        say!(
+            "Adding profile '{}' - please answer the following questions:",
+            name
+        );
+        profile.env = prompt_full_env()?;
     }
-    if !timeout.is_empty() {
-        env_obj.insert("API_TIMEOUT_MS".to_string(), Value::String(timeout));
+
+    let env = &mut profile.env;
+    if let Some(url) = &args.base_url {
+        env.set(BASE_URL, Value::String(url.clone()));
     }
-    if !small_fast_model.is_empty() {
-        env_obj.insert(
-            "ANTHROPIC_SMALL_FAST_MODEL".to_string(),
-            Value::String(small_fast_model),
-        );
+    if let Some(token) = token_from_args(args)? {
+        env.auth_token = Some(SecretSource::Text(token));
     }
-    if !disable_nonessential.is_empty() {
-        match disable_nonessential.parse::<i64>() {
-            Ok(n) => {
-                env_obj.insert(
-                    "CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC".to_string(),
-                    Value::Number(n.into()),
-                );
-            }
-            Err(_) => {
-                println!(
-                    "Warning: invalid integer for CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC; storing as string."
-                );
-                env_obj.insert(
-                    "CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC".to_string(),
-                    Value::String(disable_nonessential),
-                );
-            }
-        }
+    if let Some(model) = &args.model {
+        env.model = Some(model.clone());
+    }
+    if let Some(model) = &args.small_fast_model {
+        env.small_fast_model = Some(model.clone());
+    }
+    if let Some(timeout) = args.timeout_ms {
+        env.api_timeout_ms = Some(IntSetting {
+            value: i64::try_from(timeout)?,
+            quoted: true,
+        });
     }
 
     // Parse and add additional env variables from --env flags
-    for env_pair in env_vars {
+    for env_pair in &args.env {
         if let Some((key, value)) = env_pair.split_once('=') {
-            env_obj.insert(
-                key.trim().to_string(),
-                Value::String(value.trim().to_string()),
-            );
+            env.set(key.trim(), Value::String(value.trim().to_string()));
         } else {
-            println!(
+            say!(
                 "Warning: ignoring invalid env format '{}' (expected KEY=VALUE)",
                 env_pair
             );
         }
     }
 
-    if !env_obj.is_empty() {
-        obj.insert("env".to_string(), Value::Object(env_obj));
+    // Profiles extending another may inherit the endpoint and token
+    if profile.extends.is_empty() {
+        let env = &mut profile.env;
+        if env.base_url.is_none() {
+            if args.no_input {
+                anyhow::bail!("ANTHROPIC_BASE_URL is required (use --base-url)");
+            }
+            env.set(BASE_URL, Value::String(prompt_base_url()?));
+        }
+        if env.auth_token.is_none() && !env.extra.contains_key(API_KEY) {
+            if args.no_input {
+                anyhow::bail!(
+                    "ANTHROPIC_AUTH_TOKEN is required (use --token-stdin or --token-env)"
+                );
+            }
+            env.auth_token = Some(SecretSource::Text(prompt_token()?));
+        }
     }
 
-    let v = Value::Object(obj);
-    ensure_profiles_dir()?;
-    let p = profile_path(name);
-    fs::write(&p, serde_json::to_string_pretty(&v)?)
-        .with_context(|| format!("writing profile {}", p.display()))?;
-    println!(
+    if !args.no_validate {
+        check_issues(name, &profile_issues(&profile.to_value()))?;
+    }
+    profile.save(name)?;
+    record_created(name, args.preset.as_deref())?;
+    say!(
         "✓ Profile '{}' created successfully at {}",
         name,
-        p.display()
+        profile_path(name).display()
     );
     Ok(())
 }
@@ -189,7 +306,7 @@ pub fn get_current_profile() -> Result<Option<String>> {
 }
 
 /// Get the current active profile name for a specific project directory
-pub fn get_project_current_profile(project_dir: &PathBuf) -> Result<Option<String>> {
+pub fn get_project_current_profile(project_dir: &Path) -> Result<Option<String>> {
     if let Some((profile_name, _)) = get_project_profile_info(project_dir)? {
         Ok(Some(profile_name))
     } else {
@@ -206,13 +323,38 @@ fn set_current_profile(name: &str) -> Result<()> {
     Ok(())
 }
 
+/// How the current global profile was written to settings.json (replace if unknown)
+pub(crate) fn get_current_switch_mode() -> Result<SwitchMode> {
+    let path = current_switch_mode_path();
+    if !path.exists() {
+        return Ok(SwitchMode::Replace);
+    }
+    let mode = fs::read_to_string(&path)
+        .with_context(|| format!("reading switch mode from {}", path.display()))?;
+    SwitchMode::from_value(Value::String(mode.trim().to_string()))
+}
+
+/// Record how the current global profile was written to settings.json
+fn set_current_switch_mode(mode: SwitchMode) -> Result<()> {
+    ensure_ccm_dir()?;
+    let path = current_switch_mode_path();
+    fs::write(&path, mode.as_str())
+        .with_context(|| format!("writing switch mode to {}", path.display()))?;
+    Ok(())
+}
+
 /// Set the current active profile name for a project
-fn set_project_current_profile(project_dir: &PathBuf, name: &str) -> Result<()> {
-    set_project_profile(project_dir, name)
+fn set_project_current_profile(
+    project_dir: &Path,
+    name: &str,
+    target: ProjectTarget,
+    managed: &BTreeMap<String, ManagedKeys>,
+) -> Result<()> {
+    set_project_profile(project_dir, name, target, managed)
 }
 
-/// List all profiles
-pub fn list_profiles() -> Result<()> {
+/// Get the names of all saved profiles, sorted
+pub fn list_profile_names() -> Result<Vec<String>> {
     let dir = ensure_profiles_dir()?;
     let mut entries: Vec<_> = fs::read_dir(&dir)?
         .filter_map(|e| e.ok())
@@ -236,145 +378,737 @@ pub fn list_profiles() -> Result<()> {
         .collect();
 
     entries.sort_by_key(|e| e.file_name());
+    Ok(entries
+        .iter()
+        .filter_map(|e| {
+            e.path()
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .map(|s| s.to_string())
+        })
+        .collect())
+}
+
+/// A profile as listed by `ccm ls --output json`
+#[derive(Serialize)]
+struct ProfileSummary {
+    name: String,
+    /// Active globally
+    current: bool,
+    /// Active for the current project
+    project_current: bool,
+    base_url: Option<String>,
+    model: Option<String>,
+    #[serde(flatten)]
+    meta: ProfileMeta,
+}
+
+/// List profiles, filtered by tag and in the requested order
+pub fn list_profiles(args: &ListArgs, format: OutputFormat) -> Result<()> {
+    let dir = ensure_profiles_dir()?;
     let global_current = get_current_profile()?;
 
-    // Always check if current directory has a project profile
-    let cwd = get_current_working_dir()?;
-    let project_current = get_project_current_profile(&cwd)?;
-
-    println!("Profiles in {}:", dir.display());
-
-    for e in entries {
-        if let Some(name) = e.path().file_stem().and_then(|s| s.to_str()) {
-            let is_global_current = global_current.as_deref() == Some(name);
-            let is_project_current = project_current.as_deref() == Some(name);
-
-            if is_global_current && is_project_current {
-                // Both global and project point to same profile, just show (current)
-                println!(" - {} (current)", name);
-            } else if is_project_current {
-                println!(" - {} (current project)", name);
-            } else if is_global_current {
-                println!(" - {} (current)", name);
-            } else {
-                println!(" - {}", name);
-            }
+    // Always check if current project has a project profile
+    let project_root = current_project_root()?;
+    let project_current = get_project_current_profile(&project_root)?;
+
+    let mut profiles = Vec::new();
+    for name in list_profile_names()? {
+        // A broken sidecar shouldn't hide every profile
+        let meta = load_meta(&name).unwrap_or_else(|e| {
+            eprintln!("⚠️  Ignoring metadata of profile '{}': {:#}", name, e);
+            ProfileMeta::default()
+        });
+        if args.tag.iter().all(|tag| meta.tags.contains(tag)) {
+            profiles.push((name, meta));
+        }
+    }
+    // Names are already sorted, and the sort is stable
+    match args.sort {
+        ProfileSort::Name => {}
+        ProfileSort::LastUsed => {
+            profiles.sort_by(|(_, a), (_, b)| b.last_switched_at.cmp(&a.last_switched_at))
+        }
+        ProfileSort::MostUsed => {
+            profiles.sort_by(|(_, a), (_, b)| b.switch_count.cmp(&a.switch_count))
+        }
+        ProfileSort::Created => profiles.sort_by(|(_, a), (_, b)| b.created_at.cmp(&a.created_at)),
+    }
+
+    if !format.is_text() {
+        let profiles: Vec<ProfileSummary> = profiles
+            .into_iter()
+            .map(|(name, meta)| {
+                let env = Profile::load_resolved(&name).map(|p| p.env).ok();
+                ProfileSummary {
+                    current: global_current.as_deref() == Some(&name),
+                    project_current: project_current.as_deref() == Some(&name),
+                    base_url: env.as_ref().and_then(|e| e.base_url.clone()),
+                    model: env.and_then(|e| e.model),
+                    name,
+                    meta,
+                }
+            })
+            .collect();
+        return emit(format, &profiles);
+    }
+
+    if profiles.is_empty() && !args.tag.is_empty() {
+        say!("No profiles tagged {}", args.tag.join(", "));
+        return Ok(());
+    }
+    say!("Profiles in {}:", dir.display());
+
+    for (name, meta) in &profiles {
+        let is_global_current = global_current.as_deref() == Some(name);
+        let is_project_current = project_current.as_deref() == Some(name);
+
+        let mut line = if is_global_current && is_project_current {
+            // Both global and project point to same profile, just show (current)
+            format!(" - {} (current)", name)
+        } else if is_project_current {
+            format!(" - {} (current project)", name)
+        } else if is_global_current {
+            format!(" - {} (current)", name)
+        } else {
+            format!(" - {}", name)
+        };
+        if !args.long {
+            say!("{}", line);
+            continue;
+        }
+
+        if !meta.tags.is_empty() {
+            line.push_str(&format!(" [{}]", meta.tags.join(", ")));
+        }
+        say!("{}", line);
+        if let Some(description) = &meta.description {
+            say!("     {}", description);
+        }
+        let mut details = Vec::new();
+        if let Some(provider) = &meta.provider {
+            details.push(format!("provider {}", provider));
         }
+        if let Some(created) = &meta.created_at {
+            details.push(format!("created {}", display_time(created)));
+        }
+        details.push(match &meta.last_switched_at {
+            Some(at) => format!(
+                "last switched {} ({})",
+                display_time(at),
+                switches(meta.switch_count)
+            ),
+            None => "never switched to".to_string(),
+        });
+        say!("     {}", details.join(", "));
     }
     Ok(())
 }
 
-/// Show a profile's content
-pub fn show_profile(name: &str) -> Result<()> {
-    let p = profile_path(name);
-    let s = fs::read_to_string(&p).with_context(|| format!("reading profile {}", p.display()))?;
-    println!("{}", s);
+/// Show a profile's content, optionally with its `extends` chain flattened
+/// and its secret references resolved. Credentials are masked unless `reveal`.
+pub fn show_profile(name: &str, resolved: bool, reveal: bool, format: OutputFormat) -> Result<()> {
+    let mut value = if resolved {
+        let profile = Profile::load_resolved(name)?;
+        let profile = if reveal {
+            resolve_secrets(&profile)?
+        } else {
+            resolve_secrets_masked(&profile)?
+        };
+        profile.to_value()
+    } else {
+        let p = profile_path(name);
+        let s =
+            fs::read_to_string(&p).with_context(|| format!("reading profile {}", p.display()))?;
+        if reveal && format.is_text() {
+            say!("{}", s);
+            return Ok(());
+        }
+        serde_json::from_str(&s)
+            .with_context(|| format!("parsing profile JSON from {}", p.display()))?
+    };
+    if !reveal {
+        redact_settings(&mut value);
+    }
+    if !format.is_text() {
+        return emit(format, &value);
+    }
+    say!("{}", serde_json::to_string_pretty(&value)?);
     Ok(())
 }
 
-/// Remove a profile
-pub fn remove_profile(name: &str) -> Result<()> {
+/// Get the names of profiles that directly extend the given profile
+fn profiles_extending(name: &str) -> Result<Vec<String>> {
+    let mut children = Vec::new();
+    for other in list_profile_names()? {
+        if other != name
+            && let Ok(profile) = Profile::load(&other)
+            && profile.extends.iter().any(|p| p == name)
+        {
+            children.push(other);
+        }
+    }
+    Ok(children)
+}
+
+/// Remove a profile; returns whether it was removed
+pub fn remove_profile(name: &str) -> Result<bool> {
     // Check if the profile is currently active (global)
     if let Some(current_profile) = get_current_profile()?
         && current_profile == name
     {
-        println!(
+        say!(
             "Cannot remove profile '{}' because it is currently active (global).",
             name
         );
-        println!("Please switch to a different profile first using: ccm switch <profile_name>");
-        return Ok(());
+        say!("Please switch to a different profile first using: ccm switch <profile_name>");
+        return Ok(false);
     }
 
     // Also check if it's the current project profile
-    if let Ok(cwd) = get_current_working_dir()
-        && let Some(project_current) = get_project_current_profile(&cwd)?
+    if let Ok(project_root) = current_project_root()
+        && let Some(project_current) = get_project_current_profile(&project_root)?
         && project_current == name
     {
-        println!(
+        say!(
             "Cannot remove profile '{}' because it is currently active for this project.",
             name
         );
-        println!("Please switch to a different profile first using: ccm switch -p <profile_name>");
-        return Ok(());
+        say!("Please switch to a different profile first using: ccm switch -p <profile_name>");
+        return Ok(false);
+    }
+
+    let children = profiles_extending(name)?;
+    if !children.is_empty() {
+        say!(
+            "Cannot remove profile '{}' because other profiles extend it: {}",
+            name,
+            children.join(", ")
+        );
+        return Ok(false);
     }
 
     let p = profile_path(name);
     if p.exists() {
         fs::remove_file(&p).with_context(|| format!("removing profile {}", p.display()))?;
-        println!("Removed profile '{}'", name);
+        remove_snapshot(name)?;
+        remove_meta(name)?;
+        say!("Removed profile '{}'", name);
+        Ok(true)
     } else {
-        println!("Profile '{}' does not exist", name);
+        say!("Profile '{}' does not exist", name);
+        Ok(false)
     }
-    Ok(())
 }
 
-/// Merge profile into existing settings (profile fields override existing ones)
-fn merge_json(base: &mut Value, overlay: &Value) {
-    match (base, overlay) {
-        (Value::Object(base_map), Value::Object(overlay_map)) => {
-            for (key, overlay_value) in overlay_map {
-                match base_map.get_mut(key) {
-                    Some(base_value) => {
-                        // Recursively merge objects
-                        merge_json(base_value, overlay_value);
-                    }
-                    None => {
-                        // Key doesn't exist in base, insert it
-                        base_map.insert(key.clone(), overlay_value.clone());
-                    }
-                }
+/// Get the project settings.local.json path
+pub(crate) fn project_settings_local_path(project_dir: &Path) -> PathBuf {
+    project_dir.join(".claude").join("settings.local.json")
+}
+
+/// Get the project's shared (usually committed) settings.json path
+pub(crate) fn project_settings_shared_path(project_dir: &Path) -> PathBuf {
+    project_dir.join(".claude").join("settings.json")
+}
+
+/// Read a JSON settings file, or `None` if it doesn't exist
+pub(crate) fn read_settings_file(path: &Path) -> Result<Option<Value>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content =
+        fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let value = serde_json::from_str(&content)
+        .with_context(|| format!("parsing JSON from {}", path.display()))?;
+    Ok(Some(value))
+}
+
+/// Project settings as Claude sees them: settings.local.json over settings.json.
+/// `None` if the project has neither file.
+pub(crate) fn read_project_settings(project_dir: &Path) -> Result<Option<Value>> {
+    let shared = read_settings_file(&project_settings_shared_path(project_dir))?;
+    let local = read_settings_file(&project_settings_local_path(project_dir))?;
+    Ok(match (shared, local) {
+        (None, None) => None,
+        (shared, local) => {
+            let mut combined = shared.unwrap_or_else(|| Value::Object(Map::new()));
+            if let Some(local) = local {
+                merge_json(&mut combined, &local);
             }
+            Some(combined)
         }
-        (base, overlay) => {
-            // For non-object values, overlay replaces base
-            *base = overlay.clone();
-        }
+    })
+}
+
+/// Merge `value` into a project settings file, creating it (and `.claude/`) if
+/// needed. Returns the values the merge replaced.
+fn merge_into_settings_file(path: &Path, value: &Value) -> Result<Value> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("creating .claude dir {}", parent.display()))?;
     }
+    let mut content = read_settings_file(path)?.unwrap_or_else(|| Value::Object(Map::new()));
+
+    let written: Vec<KeyPath> = flatten(value).into_keys().collect();
+    let previous = flatten(&content)
+        .into_iter()
+        .filter(|(key, _)| written.iter().any(|w| !w.is_empty() && key.starts_with(w)))
+        .collect();
+
+    merge_json(&mut content, value);
+    fs::write(path, serde_json::to_string_pretty(&content)?)
+        .with_context(|| format!("writing {}", path.display()))?;
+    Ok(unflatten(&previous))
 }
 
-/// Get the project settings.local.json path
-fn project_settings_local_path(project_dir: &Path) -> PathBuf {
-    project_dir.join(".claude").join("settings.local.json")
+/// Write a project settings file back, or delete it if nothing is left.
+/// Returns whether it was deleted.
+fn write_or_remove_settings(path: &Path, value: &Value) -> Result<bool> {
+    if value.as_object().is_some_and(|m| m.is_empty()) {
+        fs::remove_file(path).with_context(|| format!("removing {}", path.display()))?;
+        return Ok(true);
+    }
+    fs::write(path, serde_json::to_string_pretty(value)?)
+        .with_context(|| format!("writing {}", path.display()))?;
+    Ok(false)
 }
 
-/// Handle project-level switch: merge profile into .claude/settings.local.json
-fn switch_project_profile(name: &str, profile_content: &str, profile_value: &Value) -> Result<()> {
-    let cwd = get_current_working_dir()?;
-    let local_settings_path = project_settings_local_path(&cwd);
+/// Undo a project switch in one settings file: keys the switch added are
+/// removed and keys it overwrote get their previous values back. With
+/// `keep_edits`, keys edited by hand since are left alone, with a warning.
+/// Returns `None` if nothing changed, else whether the file was deleted for
+/// being empty.
+fn restore_settings_file(
+    path: &Path,
+    managed: &ManagedKeys,
+    keep_edits: bool,
+) -> Result<Option<bool>> {
+    let Some(settings) = read_settings_file(path)? else {
+        return Ok(None);
+    };
+    let mut compared = settings.clone();
+    restore_refs(&mut compared, &managed.applied);
+    let compared = flatten(&compared);
+    let previous = flatten(&managed.previous);
+    let mut leaves = flatten(&settings);
+
+    for (key, applied) in flatten(&managed.applied) {
+        if key.is_empty() {
+            continue;
+        }
+        let current: Vec<_> = compared
+            .iter()
+            .filter(|(k, _)| k.starts_with(&key))
+            .collect();
+        if keep_edits && current != [(&key, &applied)] {
+            if current.is_empty() {
+                say!(
+                    "⚠️  '{}' was removed from {} by hand; not restoring it",
+                    path_string(&key),
+                    path.display()
+                );
+            } else {
+                say!(
+                    "⚠️  '{}' in {} was edited by hand; keeping the edited value",
+                    path_string(&key),
+                    path.display()
+                );
+            }
+            continue;
+        }
+        leaves.retain(|k, _| !k.starts_with(&key));
+        leaves.extend(
+            previous
+                .iter()
+                .filter(|(k, _)| k.starts_with(&key))
+                .map(|(k, v)| (k.clone(), v.clone())),
+        );
+    }
 
-    // Ensure .claude directory exists
-    if let Some(parent) = local_settings_path.parent() {
-        fs::create_dir_all(parent)
-            .with_context(|| format!("creating .claude dir {}", parent.display()))?;
+    let restored = unflatten(&leaves);
+    if restored == settings {
+        return Ok(None);
+    }
+    write_or_remove_settings(path, &restored).map(Some)
+}
+
+/// Split a profile's settings into what may be shared and the secret env
+/// entries (credentials and secret references) that must stay local
+fn split_secrets(profile: &Profile, unresolved: &Profile) -> (Value, Value) {
+    let mut shared = profile.to_settings_value();
+    let refs = unresolved.env.to_map();
+    let mut secrets = Map::new();
+    if let Some(env) = shared.get_mut("env").and_then(|v| v.as_object_mut()) {
+        let keys: Vec<String> = env
+            .iter()
+            .filter(|(key, value)| {
+                has_credentials(key, value) || refs.get(*key).is_some_and(needs_resolving)
+            })
+            .map(|(key, _)| key)
+            .cloned()
+            .collect();
+        for key in keys {
+            if let Some(value) = env.remove(&key) {
+                secrets.insert(key, value);
+            }
+        }
+        if env.is_empty() {
+            shared.as_object_mut().map(|m| m.remove("env"));
+        }
+    }
+    let local = if secrets.is_empty() {
+        Value::Object(Map::new())
+    } else {
+        serde_json::json!({ "env": secrets })
+    };
+    (shared, local)
+}
+
+/// What the last project switch applied, with secret references; `None` for
+/// mappings that predate recording it
+fn project_applied(mapping: &ProjectMapping) -> Option<Value> {
+    if mapping.managed.is_empty() {
+        return None;
     }
+    let mut applied = Value::Object(Map::new());
+    for managed in mapping.managed.values() {
+        merge_json(&mut applied, &managed.applied);
+    }
+    Some(applied)
+}
 
-    let final_content = if local_settings_path.exists() {
-        // Merge: read existing settings and merge profile on top
-        let existing_content = fs::read_to_string(&local_settings_path)
-            .with_context(|| format!("reading {}", local_settings_path.display()))?;
-        let mut existing_value: Value = serde_json::from_str(&existing_content)
-            .with_context(|| format!("parsing JSON from {}", local_settings_path.display()))?;
+/// The part of a project's settings at the key paths of `shape`, with its
+/// secret references restored; `None` if the project has no settings files
+fn project_profile_settings(project_dir: &Path, shape: &Value) -> Result<Option<Value>> {
+    let Some(settings) = read_project_settings(project_dir)? else {
+        return Ok(None);
+    };
+    let mut owned = owned_subset(&settings, shape);
+    restore_refs(&mut owned, shape);
+    Ok(Some(owned))
+}
 
-        merge_json(&mut existing_value, profile_value);
-        serde_json::to_string_pretty(&existing_value)?
+/// Warn if a project's settings no longer hold what its profile switch wrote.
+/// Each file is compared with what was written to it, so a key in
+/// `settings.local.json` shadowing `settings.json` is not mistaken for an edit.
+/// Returns whether they drifted.
+fn warn_project_drift(project_dir: &Path, mapping: &ProjectMapping) -> Result<bool> {
+    let claude_dir = project_dir.join(".claude");
+    // Mappings that predate recording what was written only know the profile
+    let expected: Vec<(Option<PathBuf>, Value)> = if mapping.managed.is_empty() {
+        if !profile_path(&mapping.profile).exists() {
+            return Ok(false);
+        }
+        let profile_value = Profile::load_resolved(&mapping.profile)?.to_settings_value();
+        vec![(None, profile_value)]
     } else {
-        // No existing file, use profile content directly
-        profile_content.to_string()
+        mapping
+            .managed
+            .iter()
+            .map(|(file_name, managed)| (Some(claude_dir.join(file_name)), managed.applied.clone()))
+            .collect()
     };
 
-    fs::write(&local_settings_path, &final_content)
-        .with_context(|| format!("writing {}", local_settings_path.display()))?;
+    let mut drifted = false;
+    for (path, applied) in expected {
+        let (settings, location) = match &path {
+            Some(path) => (read_settings_file(path)?, path.clone()),
+            None => (read_project_settings(project_dir)?, claude_dir.clone()),
+        };
+        let Some(settings) = settings else {
+            say!(
+                "⚠️  Project settings in {} are missing; profile '{}' is no longer applied",
+                location.display(),
+                mapping.profile
+            );
+            drifted = true;
+            continue;
+        };
+        let mut current = owned_subset(&settings, &applied);
+        restore_refs(&mut current, &applied);
+        let changes = diff(&applied, &current);
+        if changes.is_empty() {
+            continue;
+        }
+        say!(
+            "⚠️  Project settings in {} were edited since profile '{}' was switched to:",
+            location.display(),
+            mapping.profile
+        );
+        print_diff(&changes);
+        drifted = true;
+    }
+    Ok(drifted)
+}
+
+/// The parts of `value` that a project's `settings.local.json` also sets,
+/// and would therefore override
+fn shadowed_by_local(project_root: &Path, value: &Value) -> Result<Value> {
+    let Some(local) = read_settings_file(&project_settings_local_path(project_root))? else {
+        return Ok(Value::Object(Map::new()));
+    };
+    let local_keys: Vec<KeyPath> = flatten(&local).into_keys().collect();
+    let shadowed = flatten(value)
+        .into_iter()
+        .filter(|(key, _)| {
+            !key.is_empty()
+                && local_keys
+                    .iter()
+                    .any(|local| local.starts_with(key) || key.starts_with(local))
+        })
+        .collect();
+    Ok(unflatten(&shadowed))
+}
+
+/// Write a profile into a project's settings files for `target`, returning the
+/// files written and what was written to each
+fn apply_project_profile(
+    project_root: &Path,
+    profile: &Profile,
+    unresolved: &Profile,
+    target: ProjectTarget,
+) -> Result<(Vec<PathBuf>, BTreeMap<String, ManagedKeys>)> {
+    let local_settings_path = project_settings_local_path(project_root);
+
+    // (file, values to write, the same values with secret references)
+    let files = match target {
+        ProjectTarget::Local => vec![(
+            local_settings_path,
+            profile.to_settings_value(),
+            unresolved.to_settings_value(),
+        )],
+        ProjectTarget::Shared => {
+            let (shared, mut local) = split_secrets(profile, unresolved);
+            let (shared_refs, mut local_refs) = split_secrets(unresolved, unresolved);
+            // Keys already in the local file would override the shared ones, so
+            // the profile's values go there too; clearing puts the old ones back
+            let shadowed = shadowed_by_local(project_root, &shared_refs)?;
+            for key in flatten(&shadowed).into_keys() {
+                say!(
+                    "⚠️  '{}' is also set in {}; writing the profile's value there too",
+                    path_string(&key),
+                    local_settings_path.display()
+                );
+            }
+            merge_json(&mut local, &owned_subset(&shared, &shadowed));
+            merge_json(&mut local_refs, &shadowed);
+            let mut files = vec![(
+                project_settings_shared_path(project_root),
+                shared,
+                shared_refs,
+            )];
+            if local.as_object().is_some_and(|m| !m.is_empty()) {
+                files.push((local_settings_path, local, local_refs));
+            }
+            files
+        }
+    };
+
+    let mut written = Vec::new();
+    let mut managed = BTreeMap::new();
+    for (path, value, applied) in files {
+        let previous = merge_into_settings_file(&path, &value)?;
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        managed.insert(file_name, ManagedKeys { applied, previous });
+        written.push(path);
+    }
+    Ok((written, managed))
+}
+
+/// Undo what a project mapping's switch wrote to each settings file
+fn restore_project_settings(
+    project_dir: &Path,
+    mapping: &ProjectMapping,
+    keep_edits: bool,
+) -> Result<()> {
+    for (file_name, managed) in &mapping.managed {
+        restore_settings_file(
+            &project_dir.join(".claude").join(file_name),
+            managed,
+            keep_edits,
+        )?;
+    }
+    Ok(())
+}
+
+/// Handle project-level switch: merge profile into .claude/settings.local.json,
+/// or with the shared target into .claude/settings.json with secrets kept local.
+/// Returns whether it happened, since the user may cancel it.
+fn switch_project_profile(
+    name: &str,
+    profile: &Profile,
+    unresolved: &Profile,
+    target: ProjectTarget,
+) -> Result<bool> {
+    let project_root = current_project_root()?;
+
+    // Undo the previous project switch first, so its keys don't linger and
+    // the recorded previous values stay those from before any ccm switch
+    if let Some(mut mapping) = get_project_mapping(&project_root)? {
+        if warn_project_drift(&project_root, &mapping)? {
+            match prompt_switch_action("the project settings")? {
+                1 => say!("Proceeding with switch..."),
+                2 => {
+                    if !sync_project_profile()? {
+                        say!("Switch operation cancelled.");
+                        return Ok(false);
+                    }
+                    // The sync re-applied the profile and recorded it anew
+                    mapping = get_project_mapping(&project_root)?.with_context(|| {
+                        format!("reading project mapping of {}", project_root.display())
+                    })?;
+                }
+                _ => {
+                    say!("Switch operation cancelled.");
+                    return Ok(false);
+                }
+            }
+        }
+        restore_project_settings(&project_root, &mapping, true)?;
+    }
+
+    let (written, managed) = apply_project_profile(&project_root, profile, unresolved, target)?;
 
     // Track the project-profile mapping
-    set_project_current_profile(&cwd, name)?;
+    set_project_current_profile(&project_root, name, target, &managed)?;
 
-    println!(
+    let written: Vec<String> = written.iter().map(|p| p.display().to_string()).collect();
+    say!(
         "✓ Switched to profile '{}' for project {} (wrote to {})",
         name,
-        cwd.display(),
-        local_settings_path.display()
+        project_root.display(),
+        written.join(" and ")
+    );
+    Ok(true)
+}
+
+/// Read and parse `~/.claude/settings.json`, or an empty object if it doesn't exist
+pub(crate) fn read_claude_settings() -> Result<Value> {
+    let settings_path = claude_settings_path();
+    if !settings_path.exists() {
+        return Ok(Value::Object(Default::default()));
+    }
+    let content = fs::read_to_string(&settings_path)
+        .with_context(|| format!("reading settings {}", settings_path.display()))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("parsing settings JSON from {}", settings_path.display()))
+}
+
+/// Keep only the parts of `settings` at key paths the profile defines (recursive)
+pub(crate) fn owned_subset(settings: &Value, profile: &Value) -> Value {
+    match (settings, profile) {
+        (Value::Object(settings_map), Value::Object(profile_map)) => Value::Object(
+            profile_map
+                .iter()
+                .filter_map(|(key, profile_value)| {
+                    settings_map
+                        .get(key)
+                        .map(|v| (key.clone(), owned_subset(v, profile_value)))
+                })
+                .collect(),
+        ),
+        (settings, _) => settings.clone(),
+    }
+}
+
+/// The part of the global settings that belongs to the current profile, with
+/// secret references restored. If it was merged in that is only the keys of
+/// the profile and its last-applied snapshot; if it replaced the settings it is
+/// the whole file.
+pub(crate) fn current_profile_settings(
+    profile: &Profile,
+    snapshot: Option<&Value>,
+    settings: &Value,
+) -> Result<Value> {
+    let profile_value = profile.to_settings_value();
+    let mut owned = match get_current_switch_mode()? {
+        SwitchMode::Merge => {
+            let mut shape = snapshot
+                .cloned()
+                .unwrap_or_else(|| Value::Object(Map::new()));
+            merge_json(&mut shape, &profile_value);
+            owned_subset(settings, &shape)
+        }
+        SwitchMode::Replace => settings.clone(),
+    };
+    restore_refs(&mut owned, &profile_value);
+    Ok(owned)
+}
+
+/// Show a JSON value in a conflict prompt
+fn conflict_value(path: &[String], value: &Option<Value>) -> String {
+    match value {
+        Some(v) => format_value(path, v),
+        None => "(removed)".to_string(),
+    }
+}
+
+/// Ask which side wins for every key changed both in the profile and in the
+/// settings (`source` names them, e.g. `settings.json`). Returns false if the
+/// user cancelled.
+fn resolve_conflicts(profile_name: &str, merge: &mut ThreeWay, source: &str) -> Result<bool> {
+    let conflicts = std::mem::take(&mut merge.conflicts);
+    if conflicts.is_empty() {
+        return Ok(true);
+    }
+
+    say!(
+        "\n⚠️  {} key(s) changed both in profile '{}' and in {} since the last switch:",
+        conflicts.len(),
+        profile_name,
+        source
     );
+    let width = source.len() + 1;
+    for conflict in &conflicts {
+        say!("\n  {}", path_string(&conflict.path));
+        say!(
+            "    {:<width$} {}",
+            "profile:",
+            conflict_value(&conflict.path, &conflict.profile)
+        );
+        say!(
+            "    {:<width$} {}",
+            format!("{}:", source),
+            conflict_value(&conflict.path, &conflict.settings)
+        );
+        output::prompt("  Keep [p]rofile, [s]ettings, or [c]ancel? ")?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        match input.trim() {
+            "p" | "P" => merge.resolve(conflict, false),
+            "s" | "S" => merge.resolve(conflict, true),
+            _ => return Ok(false),
+        }
+    }
+    Ok(true)
+}
+
+/// Save a three-way merge result as the profile, reporting the edits from
+/// `source` (e.g. `settings.json`) it keeps
+fn save_merged_profile(
+    profile_name: &str,
+    profile_value: &Value,
+    merge: &ThreeWay,
+    source: &str,
+) -> Result<()> {
+    let merged = merge.merged_value();
+    let changes = diff(profile_value, &merged);
+    let updated = Profile::rebase(profile_name, merged)
+        .with_context(|| format!("{} is not a valid profile", source))?;
+    updated.save(profile_name)?;
+    if !changes.is_empty() {
+        say!(
+            "✓ Kept {} change(s) from {} in profile '{}':",
+            changes.len(),
+            source,
+            profile_name
+        );
+        print_diff(&changes);
+    }
     Ok(())
 }
 
@@ -391,62 +1125,90 @@ fn handle_profile_mismatch_check() -> Result<bool> {
         return Ok(true);
     }
 
-    let current_profile_path = profile_path(&current_profile_name);
-    if !current_profile_path.exists() {
+    if !profile_path(&current_profile_name).exists() {
         return Ok(true);
     }
 
-    let settings_content = fs::read_to_string(&settings_path)
-        .with_context(|| format!("reading settings {}", settings_path.display()))?;
-    let current_profile_content = fs::read_to_string(&current_profile_path)
-        .with_context(|| format!("reading profile {}", current_profile_path.display()))?;
-
-    let settings_value: Value = serde_json::from_str(&settings_content)
-        .with_context(|| format!("parsing settings JSON from {}", settings_path.display()))?;
-    let current_profile_value: Value = serde_json::from_str(&current_profile_content)
-        .with_context(|| {
-            format!(
-                "parsing profile JSON from {}",
-                current_profile_path.display()
-            )
-        })?;
+    let current_profile = Profile::load_resolved(&current_profile_name)?;
+    let current_profile_value = current_profile.to_settings_value();
+    let snapshot = load_snapshot(&current_profile_name)?;
+    let settings_value = current_profile_settings(
+        &current_profile,
+        snapshot.as_ref(),
+        &read_claude_settings()?,
+    )?;
 
     if settings_value == current_profile_value {
         return Ok(true);
     }
 
+    // With a record of what was last applied, keep the edits made in settings.json
+    // and only ask about keys that were also changed in the profile
+    if let Some(base) = snapshot {
+        let mut merge = ThreeWay::merge(&base, &current_profile_value, &settings_value);
+        if merge.settings_changes.is_empty() && merge.conflicts.is_empty() {
+            return Ok(true);
+        }
+        if !resolve_conflicts(&current_profile_name, &mut merge, "settings.json")? {
+            say!("Switch operation cancelled.");
+            return Ok(false);
+        }
+        save_merged_profile(
+            &current_profile_name,
+            &current_profile_value,
+            &merge,
+            "settings.json",
+        )?;
+        return Ok(true);
+    }
+
     display_json_diff(
         &current_profile_name,
         &current_profile_value,
         &settings_value,
     );
 
-    match prompt_switch_action()? {
+    match prompt_switch_action("settings.json")? {
         1 => {
-            println!("Proceeding with switch...");
+            say!("Proceeding with switch...");
             Ok(true)
         }
         2 => {
-            println!(
+            say!(
                 "Updating profile '{}' with current settings.json...",
                 current_profile_name
             );
-            fs::write(&current_profile_path, &settings_content)
-                .with_context(|| format!("writing profile {}", current_profile_path.display()))?;
-            println!("✓ Profile '{}' updated successfully", current_profile_name);
+            let updated =
+                Profile::rebase(&current_profile_name, settings_value).with_context(|| {
+                    format!(
+                        "settings {} is not a valid profile",
+                        settings_path.display()
+                    )
+                })?;
+            updated.save(&current_profile_name)?;
+            say!("✓ Profile '{}' updated successfully", current_profile_name);
             Ok(true)
         }
         _ => {
-            println!("Switch operation cancelled.");
+            say!("Switch operation cancelled.");
             Ok(false)
         }
     }
 }
 
-/// Handle global switch: overwrite ~/.claude/settings.json
-fn switch_global_profile(name: &str, profile_path_ref: &PathBuf) -> Result<()> {
+/// Handle global switch. Replace mode overwrites ~/.claude/settings.json; merge
+/// mode removes the previous profile's keys and merges the new profile in,
+/// leaving the rest (permissions, hooks, statusLine, ...) untouched.
+/// `applied` is the profile's settings with secret references, kept as its snapshot.
+/// Returns whether it happened, since the user may cancel it.
+fn switch_global_profile(
+    name: &str,
+    profile: &Profile,
+    applied: &Value,
+    mode: SwitchMode,
+) -> Result<bool> {
     if !handle_profile_mismatch_check()? {
-        return Ok(());
+        return Ok(false);
     }
 
     let settings = claude_settings_path();
@@ -454,64 +1216,214 @@ fn switch_global_profile(name: &str, profile_path_ref: &PathBuf) -> Result<()> {
         fs::create_dir_all(parent)
             .with_context(|| format!("creating settings parent dir {}", parent.display()))?;
     }
-    fs::copy(profile_path_ref, &settings).with_context(|| {
-        format!(
-            "copying profile {} to {}",
-            profile_path_ref.display(),
-            settings.display()
-        )
-    })?;
+
+    let content = match mode {
+        SwitchMode::Replace => profile.to_settings_value(),
+        SwitchMode::Merge => {
+            let mut content = read_claude_settings()?;
+            if let Some(previous) = get_current_profile()? {
+                let previous_value = match load_snapshot(&previous)? {
+                    Some(snapshot) => Some(snapshot),
+                    None if profile_path(&previous).exists() => {
+                        Some(Profile::load_resolved(&previous)?.to_settings_value())
+                    }
+                    None => None,
+                };
+                if let Some(previous_value) = previous_value {
+                    remove_json_keys(&mut content, &previous_value);
+                }
+            }
+            merge_json(&mut content, &profile.to_settings_value());
+            content
+        }
+    };
+    fs::write(&settings, serde_json::to_string_pretty(&content)?)
+        .with_context(|| format!("writing profile '{}' to {}", name, settings.display()))?;
 
     set_current_profile(name)?;
-    println!(
-        "✓ Switched Claude settings to profile '{}' (wrote to {})",
+    set_current_switch_mode(mode)?;
+    save_snapshot(name, applied)?;
+    let verb = match mode {
+        SwitchMode::Replace => "wrote to",
+        SwitchMode::Merge => "merged into",
+    };
+    say!(
+        "✓ Switched Claude settings to profile '{}' ({} {})",
         name,
+        verb,
         settings.display()
     );
-    Ok(())
+    Ok(true)
 }
 
-/// Switch to a profile
-pub fn switch_to_profile(name: &str, project_mode: bool) -> Result<()> {
+/// Switch to a profile, for the current project if `project_target` is given.
+/// `mode` overrides the profile's `switchMode` for a global switch.
+/// With `validate`, the profile and its parents are checked first.
+pub fn switch_to_profile(
+    name: &str,
+    project_target: Option<ProjectTarget>,
+    mode: Option<SwitchMode>,
+    validate: bool,
+) -> Result<bool> {
     let p = profile_path(name);
     if !p.exists() {
         anyhow::bail!("Profile '{}' does not exist", name);
     }
+    if validate {
+        check_profile(name)?;
+    }
 
-    let profile_content =
-        fs::read_to_string(&p).with_context(|| format!("reading profile {}", p.display()))?;
-    let profile_value: Value = serde_json::from_str(&profile_content)
-        .with_context(|| format!("parsing profile JSON from {}", p.display()))?;
+    // Flatten `extends` and validate before applying it anywhere,
+    // then swap vault references for the real secrets
+    let unresolved = Profile::load_resolved(name)?;
+    let profile = resolve_secrets(&unresolved)?;
 
-    if project_mode {
-        switch_project_profile(name, &profile_content, &profile_value)
+    let switched = if let Some(target) = project_target {
+        switch_project_profile(name, &profile, &unresolved, target)?
     } else {
-        switch_global_profile(name, &p)
+        let mode = mode.or(profile.switch_mode).unwrap_or_default();
+        switch_global_profile(name, &profile, &unresolved.to_settings_value(), mode)?
+    };
+    if switched {
+        record_switch(name);
     }
+    Ok(switched)
 }
 
-/// Launch Claude Code with current profile
-pub fn launch_claude_code() -> Result<()> {
+/// Build the `claude` command for a run.
+/// With a profile, its env is injected into the child process only and
+/// `~/.claude/settings.json` is left untouched. Without one, the current global
+/// profile is used, refreshing any secret references it holds.
+fn claude_command(profile_name: Option<&str>, args: &[String]) -> Result<Command> {
+    let mut command = Command::new("claude");
+    command.args(args);
+
+    if let Some(name) = profile_name {
+        if !profile_path(name).exists() {
+            anyhow::bail!("Profile '{}' does not exist", name);
+        }
+        let profile = resolve_secrets(&Profile::load_resolved(name)?)?;
+        say!(
+            "Launching Claude Code with profile '{}' (environment only)...",
+            name
+        );
+        command.envs(profile.env.to_env_vars());
+        return Ok(command);
+    }
+
+    // A project profile takes precedence: Claude Code reads it from .claude/
+    if let Some((project_root, project_profile)) =
+        find_project_mapping(&get_current_working_dir()?)?
+    {
+        say!(
+            "Launching Claude Code with project profile '{}' ({})...",
+            project_profile,
+            project_root.display()
+        );
+        let Some(mapping) = get_project_mapping(&project_root)? else {
+            return Ok(command);
+        };
+        if !profile_path(&project_profile).exists() {
+            say!(
+                "⚠️  Project profile '{}' no longer exists; Claude Code will use the project settings as they are",
+                project_profile
+            );
+        } else {
+            if warn_project_drift(&project_root, &mapping)? {
+                say!(
+                    "Run 'ccm sync -p' to keep these edits in the profile, or 'ccm switch -p {}' to reapply it.",
+                    project_profile
+                );
+            }
+            inject_secret_refs(&mut command, &project_profile)?;
+        }
+        return Ok(command);
+    }
+
     let current = get_current_profile()?;
 
     if current.is_none() {
         anyhow::bail!(
             "No profile is currently active.\n\
-            Please add a profile with 'ccm add <name>' and switch to it with 'ccm switch <name>' first."
+            Please add a profile with 'ccm add <name>' and switch to it with 'ccm switch <name>' first,\n\
+            or run with an explicit profile: ccm run <name>"
         );
     }
 
     let profile_name = current.unwrap();
-    println!("Launching Claude Code with profile '{}'...", profile_name);
+    say!("Launching Claude Code with profile '{}'...", profile_name);
 
-    let status = std::process::Command::new("claude").status().context(
-        "Failed to launch Claude Code. Make sure 'claude' command is available in PATH.",
-    )?;
+    if profile_path(&profile_name).exists() {
+        inject_secret_refs(&mut command, &profile_name)?;
+    }
 
-    println!("Claude Code exited with: {}", status);
+    Ok(command)
+}
+
+/// Re-resolve a profile's secret references into the child's env, so it gets
+/// fresh values even if the token was rotated since the last switch
+fn inject_secret_refs(command: &mut Command, profile_name: &str) -> Result<()> {
+    let profile = Profile::load_resolved(profile_name)?;
+    if has_secret_refs(&profile) {
+        let refs = profile.env.to_map();
+        let resolved = resolve_secrets(&profile)?.env.to_map();
+        for (key, value) in resolved {
+            if refs.get(&key).is_some_and(needs_resolving)
+                && let Some(secret) = value.as_str()
+            {
+                command.env(&key, secret);
+            }
+        }
+    }
     Ok(())
 }
 
+const LAUNCH_ERROR: &str =
+    "Failed to launch Claude Code. Make sure 'claude' command is available in PATH.";
+
+/// Launch Claude Code, wait for it to exit and return its exit code
+pub fn launch_claude_code(profile_name: Option<&str>, args: &[String]) -> Result<i32> {
+    let status = claude_command(profile_name, args)?
+        .status()
+        .context(LAUNCH_ERROR)?;
+
+    say!("Claude Code exited with: {}", status);
+    Ok(exit_code(status))
+}
+
+/// Map a child exit status to a process exit code (128 + signal when killed)
+fn exit_code(status: std::process::ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
+
+/// Run Claude Code in place of ccm and exit with its exit code.
+/// On Unix the process is replaced with `exec`, so signals go straight to
+/// claude and its exit code becomes ours.
+pub fn run_claude_code(profile_name: Option<&str>, args: &[String]) -> Result<()> {
+    let mut command = claude_command(profile_name, args)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // exec only returns on failure
+        let err = command.exec();
+        Err(err).context(LAUNCH_ERROR)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let status = command.status().context(LAUNCH_ERROR)?;
+        std::process::exit(exit_code(status));
+    }
+}
+
 /// Import current Claude settings as a new profile
 pub fn import_current_profile(name: &str) -> Result<()> {
     let settings = claude_settings_path();
@@ -523,11 +1435,15 @@ pub fn import_current_profile(name: &str) -> Result<()> {
     if p.exists() {
         anyhow::bail!("Profile '{}' already exists", name);
     }
+    let applied = read_claude_settings()?;
     fs::copy(&settings, &p)
         .with_context(|| format!("copying {} to profile {}", settings.display(), p.display()))?;
 
     set_current_profile(name)?;
-    println!(
+    set_current_switch_mode(SwitchMode::Replace)?;
+    save_snapshot(name, &applied)?;
+    record_created(name, None)?;
+    say!(
         "✓ Imported current settings to profile '{}' at {}",
         name,
         p.display()
@@ -565,21 +1481,35 @@ pub fn rename_profile(origin: &str, new: &str) -> Result<()> {
         )
     })?;
 
+    rename_snapshot(origin, new)?;
+    rename_meta(origin, new)?;
+
     // If the renamed profile was active, update the current profile reference
     if is_current {
         set_current_profile(new)?;
     }
 
-    println!("✓ Profile '{}' renamed to '{}' successfully", origin, new);
+    // Point profiles that extended the old name at the new one
+    for child in profiles_extending(origin)? {
+        let mut profile = Profile::load(&child)?;
+        for parent in profile.extends.iter_mut().filter(|p| *p == origin) {
+            *parent = new.to_string();
+        }
+        profile.save(&child)?;
+    }
+
+    say!("✓ Profile '{}' renamed to '{}' successfully", origin, new);
     Ok(())
 }
 
 /// Edit a profile using the default editor
-pub fn edit_profile(name: &str) -> Result<()> {
+pub fn edit_profile(name: &str, validate: bool) -> Result<()> {
     let profile_path = profile_path(name);
     if !profile_path.exists() {
         anyhow::bail!("Profile '{}' does not exist", name);
     }
+    let original = fs::read(&profile_path)
+        .with_context(|| format!("reading profile {}", profile_path.display()))?;
 
     // Get the editor from environment variables, fallback to common editors
     let editor = std::env::var("EDITOR")
@@ -598,25 +1528,43 @@ pub fn edit_profile(name: &str) -> Result<()> {
             }
         });
 
-    println!("Opening profile '{}' with editor: {}", name, editor);
+    say!("Opening profile '{}' with editor: {}", name, editor);
 
-    // Launch the editor with the profile file
-    let status = Command::new(&editor)
-        .arg(&profile_path)
-        .status()
-        .with_context(|| format!("Failed to launch editor '{}'", editor))?;
+    loop {
+        // Launch the editor with the profile file
+        let status = Command::new(&editor)
+            .arg(&profile_path)
+            .status()
+            .with_context(|| format!("Failed to launch editor '{}'", editor))?;
+        if !status.success() {
+            anyhow::bail!("Editor exited with error code: {:?}", status.code());
+        }
+        if !validate {
+            break;
+        }
 
-    if status.success() {
-        println!("✓ Profile '{}' edited successfully", name);
-    } else {
-        anyhow::bail!("Editor exited with error code: {:?}", status.code());
+        let issues = validate_profile(name)?;
+        match check_issues(name, &issues) {
+            Ok(()) => break,
+            Err(e) => {
+                say!("{}", e);
+                let answer = prompt_input("Re-open the editor to fix them? [y/N]: ")?;
+                if !answer.eq_ignore_ascii_case("y") {
+                    fs::write(&profile_path, &original)
+                        .with_context(|| format!("restoring profile {}", profile_path.display()))?;
+                    anyhow::bail!("Changes to profile '{}' were discarded", name);
+                }
+            }
+        }
     }
 
+    say!("✓ Profile '{}' edited successfully", name);
     Ok(())
 }
 
-/// Sync current profile with current Claude settings
-pub fn sync_profile() -> Result<()> {
+/// Sync current profile with current Claude settings; returns whether
+/// anything was written
+pub fn sync_profile() -> Result<bool> {
     let current_profile = get_current_profile()?.ok_or_else(|| {
         anyhow::anyhow!(
             "No profile is currently active.\n\
@@ -634,36 +1582,173 @@ pub fn sync_profile() -> Result<()> {
         anyhow::bail!("Current profile '{}' does not exist", current_profile);
     }
 
-    // Read both files
-    let settings_content = fs::read_to_string(&settings_path)
-        .with_context(|| format!("reading settings {}", settings_path.display()))?;
-    let profile_content = fs::read_to_string(&profile_file_path)
-        .with_context(|| format!("reading profile {}", profile_file_path.display()))?;
-
-    // Parse JSON to compare
-    let settings_value: Value = serde_json::from_str(&settings_content)
-        .with_context(|| format!("parsing settings JSON from {}", settings_path.display()))?;
-    let profile_value: Value = serde_json::from_str(&profile_content)
-        .with_context(|| format!("parsing profile JSON from {}", profile_file_path.display()))?;
+    let profile = Profile::load_resolved(&current_profile)?;
+    let profile_value = profile.to_settings_value();
+    let snapshot = load_snapshot(&current_profile)?;
+    let full_settings = read_claude_settings()?;
+    let settings_value = current_profile_settings(&profile, snapshot.as_ref(), &full_settings)?;
 
     // Compare the JSON content
     if settings_value == profile_value {
-        println!(
+        save_snapshot(&current_profile, &profile_value)?;
+        say!(
             "✓ Claude settings and current profile '{}' are already in sync",
             current_profile
         );
-        Ok(())
-    } else {
-        // Sync: update profile to match settings
-        fs::write(&profile_file_path, settings_content)
-            .with_context(|| format!("writing profile {}", profile_file_path.display()))?;
-        println!(
-            "✓ Synced current profile '{}' with Claude settings (updated {})",
+        return Ok(false);
+    }
+
+    let Some(base) = snapshot else {
+        // Nothing recorded to merge against: update profile to match settings,
+        // keeping only what it doesn't inherit
+        let updated =
+            Profile::rebase(&current_profile, settings_value.clone()).with_context(|| {
+                format!(
+                    "settings {} is not a valid profile",
+                    settings_path.display()
+                )
+            })?;
+        updated.save(&current_profile)?;
+        save_snapshot(&current_profile, &settings_value)?;
+        say!(
+            "✓ Synced current profile '{}' with Claude settings (updated {}):",
             current_profile,
             profile_file_path.display()
         );
-        Ok(())
+        print_diff(&diff(&profile_value, &settings_value));
+        return Ok(true);
+    };
+
+    // Three-way: keep edits from both sides since the last switch
+    let mut merge = ThreeWay::merge(&base, &profile_value, &settings_value);
+    if !resolve_conflicts(&current_profile, &mut merge, "settings.json")? {
+        say!("Sync cancelled.");
+        return Ok(false);
+    }
+    let merged = merge.merged_value();
+
+    if merged != profile_value {
+        save_merged_profile(&current_profile, &profile_value, &merge, "settings.json")?;
+    }
+    if merged != settings_value {
+        let applied = resolve_secrets(&Profile::try_from(merged.clone())?)?.to_settings_value();
+        let mut content = full_settings;
+        remove_json_keys(&mut content, &settings_value);
+        merge_json(&mut content, &applied);
+        fs::write(&settings_path, serde_json::to_string_pretty(&content)?)
+            .with_context(|| format!("writing {}", settings_path.display()))?;
+        let changes = diff(&settings_value, &merged);
+        say!(
+            "✓ Applied {} change(s) from profile '{}' to {}:",
+            changes.len(),
+            current_profile,
+            settings_path.display()
+        );
+        print_diff(&changes);
     }
+    save_snapshot(&current_profile, &merged)?;
+    say!(
+        "✓ Synced current profile '{}' with Claude settings",
+        current_profile
+    );
+    Ok(true)
+}
+
+/// Sync the current project's profile with its .claude settings; returns
+/// whether anything was written
+pub fn sync_project_profile() -> Result<bool> {
+    let cwd = get_current_working_dir()?;
+    let (project_root, profile_name) = find_project_mapping(&cwd)?.ok_or_else(|| {
+        anyhow::anyhow!(
+            "No project profile is set for {}.\n\
+            Please switch to a profile first using: ccm switch -p <profile_name>",
+            cwd.display()
+        )
+    })?;
+    let mapping = get_project_mapping(&project_root)?
+        .with_context(|| format!("reading project mapping of {}", project_root.display()))?;
+
+    let profile_file_path = profile_path(&profile_name);
+    if !profile_file_path.exists() {
+        anyhow::bail!("Project profile '{}' does not exist", profile_name);
+    }
+
+    let profile = Profile::load_resolved(&profile_name)?;
+    let profile_value = profile.to_settings_value();
+    let base = project_applied(&mapping);
+    let mut shape = base.clone().unwrap_or_else(|| Value::Object(Map::new()));
+    merge_json(&mut shape, &profile_value);
+    let claude_dir = project_root.join(".claude");
+    let settings_value = project_profile_settings(&project_root, &shape)?
+        .with_context(|| format!("No project settings found in {}", claude_dir.display()))?;
+
+    let Some(base) = base else {
+        if settings_value == profile_value {
+            say!(
+                "✓ Project settings and profile '{}' are already in sync",
+                profile_name
+            );
+            return Ok(false);
+        }
+        // Nothing recorded to merge against: update profile to match settings
+        let updated = Profile::rebase(&profile_name, settings_value.clone())
+            .context("project settings are not a valid profile")?;
+        updated.save(&profile_name)?;
+        say!(
+            "✓ Synced project profile '{}' with project settings (updated {}):",
+            profile_name,
+            profile_file_path.display()
+        );
+        print_diff(&diff(&profile_value, &settings_value));
+        return Ok(true);
+    };
+
+    if settings_value == profile_value && base == profile_value {
+        say!(
+            "✓ Project settings and profile '{}' are already in sync",
+            profile_name
+        );
+        return Ok(false);
+    }
+
+    // Three-way: keep edits from both sides since the switch
+    let mut merge = ThreeWay::merge(&base, &profile_value, &settings_value);
+    if !resolve_conflicts(&profile_name, &mut merge, "project settings")? {
+        say!("Sync cancelled.");
+        return Ok(false);
+    }
+    let merged = merge.merged_value();
+
+    if merged != profile_value {
+        save_merged_profile(&profile_name, &profile_value, &merge, "project settings")?;
+    }
+
+    // Re-apply the merged profile, keeping the values from before the switch
+    let unresolved = Profile::try_from(merged.clone())?;
+    restore_project_settings(&project_root, &mapping, false)?;
+    let (_, managed) = apply_project_profile(
+        &project_root,
+        &resolve_secrets(&unresolved)?,
+        &unresolved,
+        mapping.target,
+    )?;
+    set_project_current_profile(&project_root, &profile_name, mapping.target, &managed)?;
+
+    let changes = diff(&settings_value, &merged);
+    if !changes.is_empty() {
+        say!(
+            "✓ Applied {} change(s) from profile '{}' to {}:",
+            changes.len(),
+            profile_name,
+            claude_dir.display()
+        );
+        print_diff(&changes);
+    }
+    say!(
+        "✓ Synced project profile '{}' with project settings",
+        profile_name
+    );
+    Ok(true)
 }
 
 /// Remove keys from base JSON that exist in overlay JSON (recursive)
@@ -709,80 +1794,131 @@ fn remove_json_keys(base: &mut Value, overlay: &Value) {
     }
 }
 
-/// Clear project-specific profile setting (revert to using global profile)
-pub fn clear_project_profile() -> Result<()> {
-    let cwd = get_current_working_dir()?;
+/// Clear project-specific profile setting (revert to using global profile);
+/// returns whether there was one
+pub fn clear_project_profile() -> Result<bool> {
+    clear_project_profile_in(&current_project_root()?)
+}
 
-    let project_profile_name = match get_project_current_profile(&cwd)? {
-        Some(name) => name,
-        None => {
-            println!("No project-specific profile is set for {}", cwd.display());
-            return Ok(());
-        }
+/// Report what clearing a profile did to one project settings file
+fn report_settings_cleanup(path: &Path, profile_name: &str, removed: bool) {
+    if removed {
+        say!("✓ Removed {} (no remaining settings)", path.display());
+    } else {
+        say!(
+            "✓ Removed profile '{}' fields from {}",
+            profile_name,
+            path.display()
+        );
+    }
+}
+
+/// Remove a profile's keys from a project settings file, deleting the file if
+/// nothing else is left. With `only_unchanged`, keys whose value differs from
+/// the profile are kept (they were edited, or set by someone else).
+/// Used for mappings that predate recording what a switch wrote.
+fn remove_from_settings_file(
+    path: &Path,
+    profile_name: &str,
+    profile_value: &Value,
+    only_unchanged: bool,
+) -> Result<()> {
+    let Some(mut settings_value) = read_settings_file(path)? else {
+        return Ok(());
     };
+    let before = settings_value.clone();
+    if only_unchanged {
+        let mut unchanged = owned_subset(&settings_value, profile_value);
+        restore_refs(&mut unchanged, profile_value);
+        let profile_leaves = flatten(profile_value);
+        let matching: BTreeMap<KeyPath, Value> = flatten(&unchanged)
+            .into_iter()
+            .filter(|(key, value)| profile_leaves.get(key) == Some(value))
+            .collect();
+        remove_json_keys(&mut settings_value, &unflatten(&matching));
+    } else {
+        remove_json_keys(&mut settings_value, profile_value);
+    }
+    if settings_value == before {
+        return Ok(());
+    }
+
+    let removed = write_or_remove_settings(path, &settings_value)?;
+    report_settings_cleanup(path, profile_name, removed);
+    Ok(())
+}
+
+/// Clear the project-specific profile of a given directory; returns whether
+/// there was one
+pub fn clear_project_profile_in(project_dir: &Path) -> Result<bool> {
+    let Some(mapping) = get_project_mapping(project_dir)? else {
+        say!(
+            "No project-specific profile is set for {}",
+            project_dir.display()
+        );
+        return Ok(false);
+    };
+    let project_profile_name = mapping.profile;
+
+    if !mapping.managed.is_empty() {
+        // Put back exactly what the switch overwrote
+        for (file_name, managed) in &mapping.managed {
+            let path = project_dir.join(".claude").join(file_name);
+            if let Some(removed) = restore_settings_file(&path, managed, true)? {
+                report_settings_cleanup(&path, &project_profile_name, removed);
+            }
+        }
+        remove_project_profile(project_dir)?;
+        say!(
+            "✓ Cleared project-specific profile for {}. Will now use global profile.",
+            project_dir.display()
+        );
+        return Ok(true);
+    }
 
-    let local_settings_path = project_settings_local_path(&cwd);
+    let local_settings_path = project_settings_local_path(project_dir);
+    let shared_settings_path = project_settings_shared_path(project_dir);
 
     // Check if profile exists
     let profile_file_path = profile_path(&project_profile_name);
     if !profile_file_path.exists() {
-        println!(
+        say!(
             "⚠️  Profile '{}' not found. The project mapping may be corrupted.",
             project_profile_name
         );
-        println!(
-            "Please manually delete {} if needed.",
-            local_settings_path.display()
+        say!(
+            "Please manually check {} and {} if needed.",
+            local_settings_path.display(),
+            shared_settings_path.display()
         );
         // Still remove the project mapping
-        remove_project_profile(&cwd)?;
-        return Ok(());
+        remove_project_profile(project_dir)?;
+        return Ok(true);
     }
 
-    // Remove profile fields from settings.local.json
-    if local_settings_path.exists() {
-        let profile_content = fs::read_to_string(&profile_file_path)
-            .with_context(|| format!("reading profile {}", profile_file_path.display()))?;
-        let profile_value: Value = serde_json::from_str(&profile_content).with_context(|| {
-            format!("parsing profile JSON from {}", profile_file_path.display())
-        })?;
-
-        let settings_content = fs::read_to_string(&local_settings_path)
-            .with_context(|| format!("reading {}", local_settings_path.display()))?;
-        let mut settings_value: Value = serde_json::from_str(&settings_content)
-            .with_context(|| format!("parsing JSON from {}", local_settings_path.display()))?;
-
-        // Remove profile keys from settings
-        remove_json_keys(&mut settings_value, &profile_value);
-
-        // Write back or delete if empty
-        if let Value::Object(map) = &settings_value {
-            if map.is_empty() {
-                fs::remove_file(&local_settings_path)
-                    .with_context(|| format!("removing {}", local_settings_path.display()))?;
-                println!(
-                    "✓ Removed {} (no remaining settings)",
-                    local_settings_path.display()
-                );
-            } else {
-                fs::write(
-                    &local_settings_path,
-                    serde_json::to_string_pretty(&settings_value)?,
-                )
-                .with_context(|| format!("writing {}", local_settings_path.display()))?;
-                println!(
-                    "✓ Removed profile '{}' fields from {}",
-                    project_profile_name,
-                    local_settings_path.display()
-                );
-            }
-        }
+    // Remove profile fields from the project settings files. The shared file
+    // may hold the team's own choices, so only values still matching the
+    // profile are removed from it.
+    let profile_value = Profile::load_resolved(&project_profile_name)?.to_settings_value();
+    remove_from_settings_file(
+        &local_settings_path,
+        &project_profile_name,
+        &profile_value,
+        false,
+    )?;
+    if mapping.target == ProjectTarget::Shared {
+        remove_from_settings_file(
+            &shared_settings_path,
+            &project_profile_name,
+            &profile_value,
+            true,
+        )?;
     }
 
-    remove_project_profile(&cwd)?;
-    println!(
+    remove_project_profile(project_dir)?;
+    say!(
         "✓ Cleared project-specific profile for {}. Will now use global profile.",
-        cwd.display()
+        project_dir.display()
     );
-    Ok(())
+    Ok(true)
 }
@@ -0,0 +1,189 @@
+use anyhow::{Context, Result, bail};
+use serde_json::json;
+use std::fs;
+use std::path::Path;
+
+use crate::config::{
+    ProjectMapping, get_current_working_dir, list_project_mappings, profile_path,
+    project_profiles_dir,
+};
+use crate::model::Profile;
+use crate::output::{OutputFormat, emit};
+use crate::profile::{clear_project_profile_in, owned_subset, read_project_settings};
+use crate::say;
+use crate::secret::restore_refs;
+
+/// State of a project mapping on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectStatus {
+    /// The project settings hold the profile's values
+    Applied,
+    /// The project settings differ from the profile
+    Changed,
+    /// Neither settings.json nor settings.local.json exists any more
+    SettingsMissing,
+    DirectoryMissing,
+    ProfileMissing,
+}
+
+impl ProjectStatus {
+    /// Stable identifier for machine-readable output
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Applied => "applied",
+            Self::Changed => "changed",
+            Self::SettingsMissing => "settings_missing",
+            Self::DirectoryMissing => "directory_missing",
+            Self::ProfileMissing => "profile_missing",
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            Self::Applied => "applied",
+            Self::Changed => "project settings changed",
+            Self::SettingsMissing => "project settings missing",
+            Self::DirectoryMissing => "directory missing",
+            Self::ProfileMissing => "profile missing",
+        }
+    }
+
+    /// Whether the mapping points at something that no longer exists
+    pub fn is_stale(self) -> bool {
+        matches!(self, Self::DirectoryMissing | Self::ProfileMissing)
+    }
+}
+
+/// Check whether a mapped project still has its profile applied
+pub fn project_status(mapping: &ProjectMapping) -> Result<ProjectStatus> {
+    if !mapping.path.is_dir() {
+        return Ok(ProjectStatus::DirectoryMissing);
+    }
+    if !profile_path(&mapping.profile).exists() {
+        return Ok(ProjectStatus::ProfileMissing);
+    }
+    let Some(settings) = read_project_settings(&mapping.path)? else {
+        return Ok(ProjectStatus::SettingsMissing);
+    };
+
+    let profile_value = Profile::load_resolved(&mapping.profile)?.to_settings_value();
+    let mut applied = owned_subset(&settings, &profile_value);
+    restore_refs(&mut applied, &profile_value);
+
+    Ok(if applied == profile_value {
+        ProjectStatus::Applied
+    } else {
+        ProjectStatus::Changed
+    })
+}
+
+/// List every directory with a project profile
+pub fn list_projects(format: OutputFormat) -> Result<()> {
+    let mappings = list_project_mappings()?;
+    if !format.is_text() {
+        let rows: Vec<_> = mappings
+            .iter()
+            .map(|mapping| {
+                let (status, error) = match project_status(mapping) {
+                    Ok(status) => (status.as_str(), None),
+                    Err(e) => ("error", Some(format!("{:#}", e))),
+                };
+                json!({
+                    "path": mapping.path,
+                    "profile": mapping.profile,
+                    "target": mapping.target.as_str(),
+                    "status": status,
+                    "error": error,
+                })
+            })
+            .collect();
+        return emit(format, &rows);
+    }
+    if mappings.is_empty() {
+        say!("No project profiles are set");
+        return Ok(());
+    }
+    say!("Project profiles in {}:", project_profiles_dir().display());
+    for mapping in &mappings {
+        let status = match project_status(mapping) {
+            Ok(status) => status.describe().to_string(),
+            Err(e) => format!("error: {:#}", e),
+        };
+        say!(
+            " - {} → {} ({})",
+            mapping.path.display(),
+            mapping.profile,
+            status
+        );
+    }
+    Ok(())
+}
+
+/// Delete mappings whose directory or profile no longer exists
+pub fn prune_projects(format: OutputFormat) -> Result<()> {
+    let mut pruned = Vec::new();
+    for mapping in list_project_mappings()? {
+        let status = match project_status(&mapping) {
+            Ok(status) => status,
+            Err(e) => {
+                eprintln!(
+                    "⚠️  Skipping mapping {} → {}: {:#}",
+                    mapping.path.display(),
+                    mapping.profile,
+                    e
+                );
+                continue;
+            }
+        };
+        if !status.is_stale() {
+            continue;
+        }
+        fs::remove_file(&mapping.file)
+            .with_context(|| format!("removing project profile {}", mapping.file.display()))?;
+        say!(
+            "✓ Removed mapping {} → {} ({})",
+            mapping.path.display(),
+            mapping.profile,
+            status.describe()
+        );
+        pruned.push(json!({
+            "path": mapping.path,
+            "profile": mapping.profile,
+            "status": status.as_str(),
+        }));
+    }
+    if !format.is_text() {
+        return emit(format, &json!({ "removed": pruned }));
+    }
+    if pruned.is_empty() {
+        say!("No stale project mappings found");
+    }
+    Ok(())
+}
+
+/// Clear the project profile of another directory, as `ccm clear-project` does for the cwd
+pub fn clear_project(dir: &Path) -> Result<bool> {
+    if let Ok(dir) = fs::canonicalize(dir) {
+        return clear_project_profile_in(&dir);
+    }
+    // The directory is gone, so there are no settings left to clean up. Its
+    // mapping can only be found by the path it recorded.
+    let absolute = get_current_working_dir()?.join(dir);
+    let matched: Vec<ProjectMapping> = list_project_mappings()?
+        .into_iter()
+        .filter(|mapping| mapping.path == absolute)
+        .collect();
+    if matched.is_empty() {
+        bail!("No project profile is set for {}", absolute.display());
+    }
+    for mapping in matched {
+        fs::remove_file(&mapping.file)
+            .with_context(|| format!("removing project profile {}", mapping.file.display()))?;
+        say!(
+            "✓ Removed project mapping {} → {}",
+            mapping.path.display(),
+            mapping.profile
+        );
+    }
+    Ok(true)
+}
@@ -0,0 +1,117 @@
+use serde_json::Value;
+
+use crate::secret::needs_resolving;
+
+/// Env key holding extra HTTP headers, one `Name: value` per line
+const CUSTOM_HEADERS_KEY: &str = "ANTHROPIC_CUSTOM_HEADERS";
+
+const MASK: &str = "********";
+
+/// Whether a key holds a credential whose value must not be printed
+pub fn is_secret_key(key: &str) -> bool {
+    let key = key.to_ascii_uppercase();
+    key.ends_with("TOKEN")
+        || key.ends_with("_KEY")
+        || key.contains("SECRET")
+        || key.contains("PASSWORD")
+}
+
+/// Whether an env entry holds a credential: a secret key, custom headers,
+/// or a value with credentials embedded in a URL
+pub fn has_credentials(key: &str, value: &Value) -> bool {
+    is_secret_key(key)
+        || key.eq_ignore_ascii_case(CUSTOM_HEADERS_KEY)
+        || value.as_str().is_some_and(|s| redact_text(s) != s)
+}
+
+/// Mask a secret for display, keeping a short prefix like `sk-********`
+pub fn mask_secret(secret: &str) -> String {
+    let prefix: String = secret.chars().take(3).collect();
+    if secret.chars().count() <= 6 {
+        MASK.to_string()
+    } else {
+        format!("{}{}", prefix, MASK)
+    }
+}
+
+/// Mask the values of `Name: value` header lines, keeping the names
+pub fn redact_headers(headers: &str) -> String {
+    headers
+        .lines()
+        .map(|line| match line.split_once(':') {
+            Some((name, value)) if !value.trim().is_empty() => format!("{}: {}", name, MASK),
+            _ => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Mask credentials embedded in URLs anywhere in `text`: the password of
+/// `user:password@host`, or the whole user part of `token@host`
+pub fn redact_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(scheme_end) = rest.find("://") {
+        let authority_start = scheme_end + 3;
+        out.push_str(&rest[..authority_start]);
+        rest = &rest[authority_start..];
+
+        let authority_end = rest
+            .find(|c: char| matches!(c, '/' | '?' | '#') || c.is_whitespace())
+            .unwrap_or(rest.len());
+        let authority = &rest[..authority_end];
+        if let Some(at) = authority.rfind('@') {
+            match authority[..at].split_once(':') {
+                Some((user, _)) => out.push_str(&format!("{}:{}", user, MASK)),
+                None => out.push_str(MASK),
+            }
+            out.push_str(&authority[at..]);
+        } else {
+            out.push_str(authority);
+        }
+        rest = &rest[authority_end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Redact one settings value for display. Secret references and placeholders
+/// are shown as-is, since they only name where the value comes from.
+pub fn redact_value(key: &str, value: &Value) -> Value {
+    if needs_resolving(value) {
+        return value.clone();
+    }
+    match value {
+        Value::String(s) if is_secret_key(key) => Value::String(mask_secret(s)),
+        _ if is_secret_key(key) => Value::String(MASK.to_string()),
+        Value::String(s) if key.eq_ignore_ascii_case(CUSTOM_HEADERS_KEY) => {
+            Value::String(redact_headers(s))
+        }
+        Value::String(s) => Value::String(redact_text(s)),
+        other => {
+            let mut other = other.clone();
+            redact_settings(&mut other);
+            other
+        }
+    }
+}
+
+/// Redact every credential in a profile or settings object, in place
+pub fn redact_settings(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                *v = redact_value(key, v);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                match item {
+                    Value::String(s) => *s = redact_text(s),
+                    other => redact_settings(other),
+                }
+            }
+        }
+        _ => {}
+    }
+}
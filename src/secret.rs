@@ -0,0 +1,256 @@
+use anyhow::{Context, Result, anyhow, bail};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::interpolate::{expand, has_placeholders, matches_template};
+use crate::model::{CommandSource, Profile, ProfileEnv};
+use crate::redact::mask_secret;
+use crate::vault::{Vault, has_env_passphrase};
+
+/// Prefix marking an env value as a reference to a vault secret, e.g. `vault:deepseek`
+pub const VAULT_PREFIX: &str = "vault:";
+
+/// How long a secret command may run when the reference sets no `$timeout_ms`
+const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Get the vault secret name if the value is a `vault:<name>` reference
+pub fn vault_ref(value: &Value) -> Option<&str> {
+    value
+        .as_str()
+        .and_then(|s| s.strip_prefix(VAULT_PREFIX))
+        .filter(|name| !name.is_empty())
+}
+
+/// Check whether an env value is a secret reference: a vault secret or a command
+pub fn is_secret_ref(value: &Value) -> bool {
+    vault_ref(value).is_some() || matches!(CommandSource::from_value("", value), Ok(Some(_)))
+}
+
+/// Check whether an env value is replaced when the profile is applied: a
+/// secret reference, or text with `${VAR}` placeholders
+pub fn needs_resolving(value: &Value) -> bool {
+    is_secret_ref(value) || has_placeholders(value)
+}
+
+/// Check whether any env value of the profile needs resolving
+pub fn has_secret_refs(profile: &Profile) -> bool {
+    profile.env.to_map().values().any(needs_resolving)
+}
+
+/// Whether an applied env value may be what the profile's value resolved to.
+/// Secrets are not resolved to compare, so a secret reference always matches;
+/// a placeholder matches any expansion of it, since the variables may have
+/// changed since it was applied.
+pub fn matches_applied(profile_value: &Value, applied: &Value) -> bool {
+    if is_secret_ref(profile_value) {
+        return true;
+    }
+    match profile_value
+        .as_str()
+        .filter(|_| has_placeholders(profile_value))
+    {
+        Some(text) => applied
+            .as_str()
+            .is_some_and(|applied| matches_template(text, applied)),
+        None => profile_value == applied,
+    }
+}
+
+/// How references are resolved
+#[derive(Clone, Copy, PartialEq)]
+enum Resolve {
+    Plain,
+    /// Values masked for display
+    Masked,
+    /// Never prompt: commands get no stdin, and vault references are skipped
+    /// unless CCM_VAULT_PASSPHRASE is set
+    Unattended,
+}
+
+/// Results of secret commands, so each command runs at most once per ccm invocation
+fn command_cache() -> &'static Mutex<HashMap<String, String>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Run a secret command through the shell and return the first line of its output
+fn run_secret_command(key: &str, source: &CommandSource, interactive: bool) -> Result<String> {
+    if let Some(cached) = command_cache().lock().unwrap().get(&source.cmd) {
+        return Ok(cached.clone());
+    }
+
+    let timeout = source
+        .timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_COMMAND_TIMEOUT);
+
+    let mut command = if cfg!(windows) {
+        let mut c = Command::new("cmd");
+        c.arg("/C");
+        c
+    } else {
+        let mut c = Command::new("sh");
+        c.arg("-c");
+        c
+    };
+    let mut child = command
+        .arg(&source.cmd)
+        .stdin(if interactive {
+            Stdio::inherit()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .with_context(|| format!("env.{}: running secret command '{}'", key, source.cmd))?;
+
+    // Read output on a separate thread so a chatty command can't block on a full pipe
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let reader = thread::spawn(move || {
+        let mut output = String::new();
+        stdout.read_to_string(&mut output).map(|_| output)
+    });
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if started.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            bail!(
+                "env.{}: secret command '{}' timed out after {} ms",
+                key,
+                source.cmd,
+                timeout.as_millis()
+            );
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+
+    if !status.success() {
+        bail!(
+            "env.{}: secret command '{}' failed ({})",
+            key,
+            source.cmd,
+            status
+        );
+    }
+    let output = reader
+        .join()
+        .map_err(|_| anyhow!("env.{}: reading secret command output", key))?
+        .with_context(|| format!("env.{}: reading secret command output", key))?;
+    let secret = output.lines().next().unwrap_or("").trim().to_string();
+    if secret.is_empty() {
+        bail!(
+            "env.{}: secret command '{}' printed nothing",
+            key,
+            source.cmd
+        );
+    }
+
+    command_cache()
+        .lock()
+        .unwrap()
+        .insert(source.cmd.clone(), secret.clone());
+    Ok(secret)
+}
+
+fn resolve(profile: &Profile, mode: Resolve) -> Result<Profile> {
+    if !has_secret_refs(profile) {
+        return Ok(profile.clone());
+    }
+
+    // The vault is only unlocked when the profile actually references it
+    let mut vault: Option<Vault> = None;
+    let mut env = profile.env.to_map();
+    let mut skipped = Vec::new();
+    for (key, value) in env.iter_mut() {
+        let secret = if let Some(name) = vault_ref(value) {
+            if vault.is_none() && mode == Resolve::Unattended && !has_env_passphrase() {
+                eprintln!(
+                    "ccm: env.{}: skipped vault secret '{}' (set CCM_VAULT_PASSPHRASE to unlock the vault without a prompt)",
+                    key, name
+                );
+                skipped.push(key.clone());
+                continue;
+            }
+            if vault.is_none() {
+                vault = Some(Vault::open_existing()?);
+            }
+            vault
+                .as_ref()
+                .and_then(|v| v.get(name))
+                .ok_or_else(|| anyhow!("env.{}: secret '{}' not found in vault", key, name))?
+                .to_string()
+        } else if let Some(source) = CommandSource::from_value(key, value)? {
+            run_secret_command(key, &source, mode != Resolve::Unattended)?
+        } else if let Some(text) = value.as_str().filter(|_| has_placeholders(value)) {
+            // Expanded values are not secrets as such; display masks them by key
+            let expanded = expand(text).map_err(|e| anyhow!("env.{}: {}", key, e))?;
+            *value = Value::String(expanded);
+            continue;
+        } else {
+            continue;
+        };
+        *value = Value::String(if mode == Resolve::Masked {
+            mask_secret(&secret)
+        } else {
+            secret
+        });
+    }
+    for key in skipped {
+        env.remove(&key);
+    }
+
+    let mut resolved = profile.clone();
+    resolved.env = ProfileEnv::from_map(env);
+    Ok(resolved)
+}
+
+/// Return a copy of the profile with every secret reference replaced by its
+/// value and every `${VAR}` placeholder expanded
+pub fn resolve_secrets(profile: &Profile) -> Result<Profile> {
+    resolve(profile, Resolve::Plain)
+}
+
+/// Like `resolve_secrets`, but with the resolved values masked for display.
+/// Still runs every command, so it shows whether the references work.
+pub fn resolve_secrets_masked(profile: &Profile) -> Result<Profile> {
+    resolve(profile, Resolve::Masked)
+}
+
+/// Like `resolve_secrets`, but never prompts, for the shell hook whose output
+/// is evaluated by the shell. Vault references are left out (with a warning)
+/// unless CCM_VAULT_PASSPHRASE is set, and commands run without stdin.
+pub fn resolve_secrets_unattended(profile: &Profile) -> Result<Profile> {
+    resolve(profile, Resolve::Unattended)
+}
+
+/// Put the profile's secret references and placeholders back into applied
+/// settings, so that settings written with resolved values compare equal to
+/// the profile and syncing them back never copies a plaintext token into the
+/// profile. Values edited since they were expanded are left alone.
+pub fn restore_refs(settings: &mut Value, profile: &Value) {
+    let Some(profile_env) = profile.get("env").and_then(|v| v.as_object()) else {
+        return;
+    };
+    let Some(settings_env) = settings.get_mut("env").and_then(|v| v.as_object_mut()) else {
+        return;
+    };
+    for (key, value) in profile_env {
+        if needs_resolving(value)
+            && let Some(applied) = settings_env.get_mut(key)
+            && matches_applied(value, applied)
+        {
+            *applied = value.clone();
+        }
+    }
+}
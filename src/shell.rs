@@ -0,0 +1,157 @@
+use anyhow::{Result, bail};
+use clap::ValueEnum;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::env;
+
+use crate::config::profile_path;
+use crate::model::Profile;
+use crate::output::{OutputFormat, emit};
+use crate::secret::resolve_secrets;
+
+/// Output syntax for environment exports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    #[value(alias = "powershell")]
+    Pwsh,
+    Dotenv,
+}
+
+impl Shell {
+    /// Guess the shell from $SHELL, falling back to bash (PowerShell on Windows)
+    pub fn detect() -> Self {
+        if cfg!(windows) {
+            return Self::Pwsh;
+        }
+        let shell = env::var("SHELL").unwrap_or_default();
+        match shell.rsplit('/').next().unwrap_or("") {
+            "fish" => Self::Fish,
+            "zsh" => Self::Zsh,
+            "pwsh" | "powershell" => Self::Pwsh,
+            _ => Self::Bash,
+        }
+    }
+}
+
+/// Check that a key can be used as an environment variable name in every shell
+pub fn validate_env_key(key: &str) -> Result<()> {
+    let mut chars = key.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        bail!("'{}' is not a valid environment variable name", key);
+    }
+    Ok(())
+}
+
+/// POSIX single quoting: everything is literal except `'`, written as `'\''`
+fn quote_posix(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// fish single quoting: only `\` and `'` need escaping
+fn quote_fish(value: &str) -> String {
+    format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+/// PowerShell single quoting: `'` is doubled
+fn quote_pwsh(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// dotenv: bare when safe, otherwise double-quoted with backslash escapes
+fn quote_dotenv(value: &str) -> String {
+    let is_bare = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:@%+,=".contains(c));
+    if is_bare {
+        return value.to_string();
+    }
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str(r"\\"),
+            '"' => out.push_str("\\\""),
+            '$' => out.push_str(r"\$"),
+            '\n' => out.push_str(r"\n"),
+            '\r' => out.push_str(r"\r"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// A line that sets `key` to `value` in the given shell
+pub fn export_line(shell: Shell, key: &str, value: &str) -> String {
+    match shell {
+        Shell::Bash | Shell::Zsh => format!("export {}={}", key, quote_posix(value)),
+        Shell::Fish => format!("set -gx {} {}", key, quote_fish(value)),
+        Shell::Pwsh => format!("$env:{} = {}", key, quote_pwsh(value)),
+        Shell::Dotenv => format!("{}={}", key, quote_dotenv(value)),
+    }
+}
+
+/// A line that removes `key` from the environment in the given shell
+pub fn unset_line(shell: Shell, key: &str) -> Result<String> {
+    Ok(match shell {
+        Shell::Bash | Shell::Zsh => format!("unset {}", key),
+        Shell::Fish => format!("set -e {}", key),
+        Shell::Pwsh => format!("Remove-Item Env:{} -ErrorAction SilentlyContinue", key),
+        Shell::Dotenv => bail!("--unset is not supported for dotenv output"),
+    })
+}
+
+/// Print a profile's env as `eval`-able exports (or unsets) for the given shell
+pub fn print_profile_env(
+    name: &str,
+    shell: Option<Shell>,
+    unset: bool,
+    format: OutputFormat,
+) -> Result<()> {
+    if !profile_path(name).exists() {
+        bail!("Profile '{}' does not exist", name);
+    }
+    let shell = shell.unwrap_or_else(Shell::detect);
+    let profile = Profile::load_resolved(name)?;
+
+    // The variables themselves, for scripts that set them on their own
+    if !format.is_text() {
+        return if unset {
+            let keys: Vec<String> = profile.env.to_map().keys().cloned().collect();
+            emit(format, &json!({ "unset": keys }))
+        } else {
+            let vars: BTreeMap<String, String> = resolve_secrets(&profile)?
+                .env
+                .to_env_vars()
+                .into_iter()
+                .collect();
+            emit(format, &json!({ "env": vars }))
+        };
+    }
+
+    // Build every line first so an invalid key never leaves a half-applied `eval`
+    let mut lines = Vec::new();
+    if unset {
+        for key in profile.env.to_map().keys() {
+            validate_env_key(key)?;
+            lines.push(unset_line(shell, key)?);
+        }
+    } else {
+        for (key, value) in resolve_secrets(&profile)?.env.to_env_vars() {
+            validate_env_key(&key)?;
+            lines.push(export_line(shell, &key, &value));
+        }
+    }
+
+    for line in lines {
+        println!("{}", line);
+    }
+    Ok(())
+}
@@ -0,0 +1,187 @@
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+
+use crate::config::applied_snapshot_path;
+
+/// A key path into a settings object, e.g. `["env", "ANTHROPIC_MODEL"]`
+pub type KeyPath = Vec<String>;
+
+/// Display a key path as `env.ANTHROPIC_MODEL`
+pub fn path_string(path: &[String]) -> String {
+    path.join(".")
+}
+
+/// Flatten an object into its leaf values by key path.
+/// Arrays, scalars and empty objects are leaves.
+pub fn flatten(value: &Value) -> BTreeMap<KeyPath, Value> {
+    fn walk(value: &Value, path: &mut KeyPath, out: &mut BTreeMap<KeyPath, Value>) {
+        match value {
+            Value::Object(map) if !map.is_empty() => {
+                for (key, v) in map {
+                    path.push(key.clone());
+                    walk(v, path, out);
+                    path.pop();
+                }
+            }
+            leaf => {
+                out.insert(path.clone(), leaf.clone());
+            }
+        }
+    }
+    let mut out = BTreeMap::new();
+    walk(value, &mut Vec::new(), &mut out);
+    out
+}
+
+/// Rebuild an object from leaf values produced by `flatten`
+pub fn unflatten(leaves: &BTreeMap<KeyPath, Value>) -> Value {
+    let mut root = Value::Object(Map::new());
+    for (path, value) in leaves {
+        // The empty path only appears for an empty root object
+        let Some((last, parents)) = path.split_last() else {
+            continue;
+        };
+        let mut node = &mut root;
+        for key in parents {
+            if !node.is_object() {
+                *node = Value::Object(Map::new());
+            }
+            node = node
+                .as_object_mut()
+                .expect("just made an object")
+                .entry(key.clone())
+                .or_insert_with(|| Value::Object(Map::new()));
+        }
+        if !node.is_object() {
+            *node = Value::Object(Map::new());
+        }
+        node.as_object_mut()
+            .expect("just made an object")
+            .insert(last.clone(), value.clone());
+    }
+    root
+}
+
+/// A key both sides changed, to different values (`None` means removed)
+#[derive(Debug)]
+pub struct Conflict {
+    pub path: KeyPath,
+    pub profile: Option<Value>,
+    pub settings: Option<Value>,
+}
+
+/// Result of merging profile and settings edits made since the last switch
+#[derive(Debug, Default)]
+pub struct ThreeWay {
+    /// Leaves both sides agree on, plus every non-conflicting edit
+    pub merged: BTreeMap<KeyPath, Value>,
+    /// Keys changed only in the profile
+    pub profile_changes: Vec<KeyPath>,
+    /// Keys changed only in settings.json
+    pub settings_changes: Vec<KeyPath>,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl ThreeWay {
+    /// Merge key-level edits of `profile` and `settings` relative to `base`,
+    /// the snapshot of what ccm last wrote
+    pub fn merge(base: &Value, profile: &Value, settings: &Value) -> Self {
+        let base = flatten(base);
+        let profile = flatten(profile);
+        let settings = flatten(settings);
+        let paths: BTreeSet<&KeyPath> = base
+            .keys()
+            .chain(profile.keys())
+            .chain(settings.keys())
+            .collect();
+
+        let mut result = Self::default();
+        for path in paths {
+            let (b, p, s) = (base.get(path), profile.get(path), settings.get(path));
+            let chosen = if p == s {
+                p
+            } else if p == b {
+                result.settings_changes.push(path.clone());
+                s
+            } else if s == b {
+                result.profile_changes.push(path.clone());
+                p
+            } else {
+                result.conflicts.push(Conflict {
+                    path: path.clone(),
+                    profile: p.cloned(),
+                    settings: s.cloned(),
+                });
+                continue;
+            };
+            if let Some(value) = chosen {
+                result.merged.insert(path.clone(), value.clone());
+            }
+        }
+        result
+    }
+
+    /// Settle a conflict with one side's value, counting it as that side's change
+    pub fn resolve(&mut self, conflict: &Conflict, take_settings: bool) {
+        let value = if take_settings {
+            self.settings_changes.push(conflict.path.clone());
+            &conflict.settings
+        } else {
+            self.profile_changes.push(conflict.path.clone());
+            &conflict.profile
+        };
+        if let Some(value) = value {
+            self.merged.insert(conflict.path.clone(), value.clone());
+        }
+    }
+
+    pub fn merged_value(&self) -> Value {
+        unflatten(&self.merged)
+    }
+}
+
+/// Load what ccm last wrote to settings.json for a profile, if recorded
+pub fn load_snapshot(name: &str) -> Result<Option<Value>> {
+    let path = applied_snapshot_path(name);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("reading snapshot {}", path.display()))?;
+    let value = serde_json::from_str(&content)
+        .with_context(|| format!("parsing snapshot {}", path.display()))?;
+    Ok(Some(value))
+}
+
+/// Record what ccm wrote to settings.json for a profile (with secret references, never secrets)
+pub fn save_snapshot(name: &str, value: &Value) -> Result<()> {
+    let path = applied_snapshot_path(name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("creating snapshot dir {}", parent.display()))?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(value)?)
+        .with_context(|| format!("writing snapshot {}", path.display()))?;
+    Ok(())
+}
+
+/// Forget a profile's snapshot
+pub fn remove_snapshot(name: &str) -> Result<()> {
+    let path = applied_snapshot_path(name);
+    if path.exists() {
+        fs::remove_file(&path).with_context(|| format!("removing snapshot {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Move a profile's snapshot along with a rename
+pub fn rename_snapshot(origin: &str, new: &str) -> Result<()> {
+    let origin_path = applied_snapshot_path(origin);
+    if origin_path.exists() {
+        fs::rename(&origin_path, applied_snapshot_path(new))
+            .with_context(|| format!("renaming snapshot {}", origin_path.display()))?;
+    }
+    Ok(())
+}
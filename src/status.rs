@@ -0,0 +1,276 @@
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::path::PathBuf;
+
+use crate::config::{
+    claude_settings_path, current_project_root, get_project_mapping, profile_path,
+};
+use crate::diff::format_value;
+use crate::model::{
+    API_KEY, API_TIMEOUT_MS, AUTH_TOKEN, BASE_URL, DISABLE_NONESSENTIAL_TRAFFIC, MODEL, Profile,
+    SMALL_FAST_MODEL, SwitchMode,
+};
+use crate::output::{OutputFormat, emit};
+use crate::profile::{
+    get_current_profile, get_current_switch_mode, project_settings_local_path,
+    project_settings_shared_path, read_settings_file,
+};
+use crate::redact::redact_value;
+use crate::say;
+use crate::secret::matches_applied;
+use crate::snapshot::{KeyPath, flatten, load_snapshot, path_string};
+
+/// Env vars Claude Code reads from the shell even when no settings file sets them
+const SHELL_KEYS: &[&str] = &[
+    BASE_URL,
+    AUTH_TOKEN,
+    API_KEY,
+    MODEL,
+    SMALL_FAST_MODEL,
+    API_TIMEOUT_MS,
+    DISABLE_NONESSENTIAL_TRAFFIC,
+];
+
+/// Where a setting can come from, lowest precedence first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    /// ~/.claude/settings.json
+    User,
+    /// .claude/settings.json of the project
+    Project,
+    /// .claude/settings.local.json of the project
+    ProjectLocal,
+    /// The shell environment, which overrides every settings file
+    Shell,
+}
+
+/// One source's values, and what a ccm profile wrote there
+struct Layer {
+    source: Source,
+    file: Option<PathBuf>,
+    label: String,
+    values: BTreeMap<KeyPath, Value>,
+    /// The profile that was applied here and its values (with secret references)
+    profile: Option<(String, BTreeMap<KeyPath, Value>)>,
+}
+
+/// An effective key, where it came from and which sources it shadows
+#[derive(Serialize)]
+struct Entry {
+    key: String,
+    /// Masked for credentials
+    value: Value,
+    source: Source,
+    file: Option<PathBuf>,
+    /// The ccm profile that wrote the value, if any
+    profile: Option<String>,
+    /// Whether the value was changed by hand after the profile wrote it
+    edited: bool,
+    overrides: Vec<Source>,
+}
+
+fn layer(
+    source: Source,
+    file: PathBuf,
+    label: String,
+    profile: Option<(String, Value)>,
+) -> Result<Layer> {
+    let values = read_settings_file(&file)?
+        .map(|v| flatten(&v))
+        .unwrap_or_default();
+    Ok(Layer {
+        source,
+        file: Some(file),
+        label,
+        values,
+        profile: profile.map(|(name, applied)| (name, flatten(&applied))),
+    })
+}
+
+/// What the global profile last wrote to settings.json, with secret references
+fn global_applied(name: &str) -> Result<Option<Value>> {
+    if let Some(snapshot) = load_snapshot(name)? {
+        return Ok(Some(snapshot));
+    }
+    if profile_path(name).exists() {
+        return Ok(Some(Profile::load_resolved(name)?.to_settings_value()));
+    }
+    Ok(None)
+}
+
+/// Explain the effective Claude Code configuration for the current directory:
+/// the active profiles, and for every key the file (or shell) it comes from
+pub fn show_status(format: OutputFormat) -> Result<()> {
+    let global = get_current_profile()?;
+    let mode = get_current_switch_mode()?;
+    let project_root = current_project_root()?;
+    let mapping = get_project_mapping(&project_root)?;
+
+    let mut layers = Vec::new();
+    let global_profile = match &global {
+        Some(name) => global_applied(name)?.map(|applied| (name.clone(), applied)),
+        None => None,
+    };
+    layers.push(layer(
+        Source::User,
+        claude_settings_path(),
+        claude_settings_path().display().to_string(),
+        global_profile,
+    )?);
+    for (source, path) in [
+        (Source::Project, project_settings_shared_path(&project_root)),
+        (
+            Source::ProjectLocal,
+            project_settings_local_path(&project_root),
+        ),
+    ] {
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let profile = mapping.as_ref().and_then(|m| {
+            m.managed
+                .get(&file_name)
+                .map(|managed| (m.profile.clone(), managed.applied.clone()))
+        });
+        layers.push(layer(
+            source,
+            path,
+            format!(".claude/{}", file_name),
+            profile,
+        )?);
+    }
+
+    // Shell variables for every env key a file sets, plus the ones Claude Code reads anyway
+    let mut env_keys: BTreeSet<String> = SHELL_KEYS.iter().map(|k| k.to_string()).collect();
+    for layer in &layers {
+        for key in layer.values.keys() {
+            if let [section, name] = key.as_slice()
+                && section == "env"
+            {
+                env_keys.insert(name.clone());
+            }
+        }
+    }
+    let shell_values = env_keys
+        .into_iter()
+        .filter_map(|key| {
+            env::var(&key)
+                .ok()
+                .map(|value| (vec!["env".to_string(), key], Value::String(value)))
+        })
+        .collect();
+    layers.push(Layer {
+        source: Source::Shell,
+        file: None,
+        label: "shell environment".to_string(),
+        values: shell_values,
+        profile: None,
+    });
+
+    let keys: BTreeSet<&KeyPath> = layers.iter().flat_map(|l| l.values.keys()).collect();
+    let mut entries = Vec::new();
+    let mut labels = Vec::new();
+    for key in keys {
+        let defining: Vec<&Layer> = layers
+            .iter()
+            .rev()
+            .filter(|l| l.values.contains_key(key))
+            .collect();
+        let Some((winner, shadowed)) = defining.split_first() else {
+            continue;
+        };
+        let value = &winner.values[key];
+        let (profile, edited) = match &winner.profile {
+            Some((name, applied)) => match applied.get(key) {
+                Some(expected) => (Some(name.clone()), !matches_applied(expected, value)),
+                None => (None, false),
+            },
+            None => (None, false),
+        };
+        labels.push((
+            format_value(key, value),
+            winner.label.clone(),
+            shadowed.iter().map(|l| l.label.clone()).collect::<Vec<_>>(),
+        ));
+        entries.push(Entry {
+            key: path_string(key),
+            value: redact_value(key.last().map_or("", |k| k.as_str()), value),
+            source: winner.source,
+            file: winner.file.clone(),
+            profile,
+            edited,
+            overrides: shadowed.iter().map(|l| l.source).collect(),
+        });
+    }
+
+    if !format.is_text() {
+        return emit(
+            format,
+            &json!({
+                "global_profile": global,
+                "switch_mode": global.as_ref().map(|_| mode.as_str()),
+                "project": {
+                    "path": project_root,
+                    "profile": mapping.as_ref().map(|m| &m.profile),
+                    "target": mapping.as_ref().map(|m| m.target.as_str()),
+                },
+                "settings": entries,
+            }),
+        );
+    }
+
+    match &global {
+        Some(name) => {
+            let how = match mode {
+                SwitchMode::Replace => "written to",
+                SwitchMode::Merge => "merged into",
+            };
+            say!(
+                "Global profile:  {} ({} {})",
+                name,
+                how,
+                claude_settings_path().display()
+            );
+        }
+        None => say!("Global profile:  none"),
+    }
+    match &mapping {
+        Some(m) => say!(
+            "Project:         {} → {} ({} target)",
+            project_root.display(),
+            m.profile,
+            m.target.as_str()
+        ),
+        None => say!(
+            "Project:         {} (no project profile)",
+            project_root.display()
+        ),
+    }
+
+    if entries.is_empty() {
+        say!("\nNo settings found for Claude Code here");
+        return Ok(());
+    }
+    say!("\nEffective settings:");
+    for (entry, (value, label, shadowed)) in entries.iter().zip(labels) {
+        let mut origin = label;
+        if let Some(profile) = &entry.profile {
+            origin.push_str(&format!(", profile '{}'", profile));
+            if entry.edited {
+                origin.push_str(", edited since the switch");
+            }
+        }
+        say!("  {} = {}", entry.key, value);
+        if shadowed.is_empty() {
+            say!("      from {}", origin);
+        } else {
+            say!("      from {}; overrides {}", origin, shadowed.join(", "));
+        }
+    }
+    Ok(())
+}
@@ -12,15 +12,18 @@ use ratatui::{
     text::{Line, Span},
     widgets::{Block, BorderType, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
 };
-use serde_json::Value;
 use std::io::IsTerminal;
 use std::{fs, io};
 
-use crate::config::{ensure_profiles_dir, profile_path};
+use crate::cli::AddArgs;
+use crate::config::ensure_profiles_dir;
+use crate::model::Profile;
 use crate::profile::{
-    add_profile_interactive, get_current_profile, launch_claude_code, remove_profile,
-    rename_profile, switch_to_profile,
+    add_profile, get_current_profile, launch_claude_code, remove_profile, rename_profile,
+    switch_to_profile,
 };
+use crate::redact::redact_settings;
+use crate::validate::chain_errors;
 
 /// Application state for the TUI
 pub struct App {
@@ -647,7 +650,7 @@ impl TuiApp {
     }
 
     fn render_message_popup(f: &mut Frame, area: Rect, message: &str) {
-        let content = vec![Line::from(message)];
+        let content: Vec<Line> = message.lines().map(Line::from).collect();
 
         let paragraph = Paragraph::new(content)
             .block(
@@ -685,27 +688,14 @@ impl TuiApp {
     }
 
     pub fn get_profile_details_static(profile_name: &str) -> Result<Vec<Line<'static>>> {
-        let profile_path = profile_path(profile_name);
-        let content = fs::read_to_string(&profile_path)
-            .with_context(|| format!("reading profile {}", profile_path.display()))?;
-
-        let json: Value = serde_json::from_str(&content)?;
-
-        // 创建一个JSON副本，用于隐藏敏感信息
-        let mut display_json = json.clone();
+        let mut value = Profile::load_resolved(profile_name)?.to_value();
 
         // 隐藏token
-        if let Some(env_obj) = display_json.get_mut("env").and_then(|v| v.as_object_mut()) {
-            for (key, value) in env_obj.iter_mut() {
-                if key.contains("AUTH_TOKEN") || key.contains("TOKEN") {
-                    *value = Value::String("••••••••••••••••".to_string());
-                }
-            }
-        }
+        redact_settings(&mut value);
 
         // 将处理后的JSON格式化为pretty printed string
-        let display_content = serde_json::to_string_pretty(&display_json)
-            .with_context(|| "failed to serialize json")?;
+        let display_content =
+            serde_json::to_string_pretty(&value).with_context(|| "failed to serialize json")?;
 
         // 解析JSON并添加语法高亮
         let lines = Self::highlight_json(&display_content);
@@ -836,7 +826,7 @@ impl TuiApp {
                         if !self.app.input_buffer.is_empty() {
                             let profile_name = self.app.input_buffer.clone();
                             // Add profile using existing function
-                            if let Err(e) = add_profile_interactive(&profile_name, &[]) {
+                            if let Err(e) = add_profile(&profile_name, &AddArgs::default()) {
                                 self.app
                                     .show_message(format!("Failed to add profile: {}", e));
                             } else {
@@ -891,9 +881,22 @@ impl TuiApp {
                     KeyCode::Enter => {
                         if self.app.popup_selection {
                             // Yes selected - TUI uses global mode by default
-                            if let Err(e) = switch_to_profile(profile_name, false) {
-                                self.app
-                                    .show_message(format!("Failed to switch profile: {}", e));
+                            let result = switch_to_profile(profile_name, None, None, true);
+                            self.app.show_popup = None;
+                            if let Err(e) = result {
+                                // Keep validation errors on screen until dismissed
+                                let errors = chain_errors(profile_name);
+                                let mut message = format!("Failed to switch profile: {}", e);
+                                if errors.is_empty() {
+                                    self.app.show_message(message);
+                                } else {
+                                    message.push('\n');
+                                    for error in errors {
+                                        message.push('\n');
+                                        message.push_str(&error);
+                                    }
+                                    self.app.show_popup = Some(PopupType::Message(message));
+                                }
                             } else {
                                 self.app.show_message(format!(
                                     "Switched to profile '{}'",
@@ -901,8 +904,9 @@ impl TuiApp {
                                 ));
                                 self.app.refresh_profiles()?;
                             }
+                        } else {
+                            self.app.show_popup = None;
                         }
-                        self.app.show_popup = None;
                         self.app.popup_selection = true; // Reset to default
                     }
                     KeyCode::Esc => {
@@ -1000,12 +1004,17 @@ impl TuiApp {
                 self.app.input_buffer.clear();
                 self.app.show_popup = Some(PopupType::AddProfile);
             }
-            KeyCode::Char('l') => {
-                if let Err(e) = launch_claude_code() {
+            KeyCode::Char('l') => match launch_claude_code(None, &[]) {
+                Ok(0) => {}
+                Ok(code) => {
+                    self.app
+                        .show_message(format!("Claude Code exited with code {}", code));
+                }
+                Err(e) => {
                     self.app
                         .show_message(format!("Failed to launch Claude Code: {}", e));
                 }
-            }
+            },
             KeyCode::Char('q') => {
                 self.app.should_quit = true;
             }
@@ -1085,24 +1094,15 @@ pub fn demo_tui() -> Result<()> {
         println!("📄 Sample Profile: {}", profile_name);
         println!("{}", "─".repeat(60));
 
-        // Show the original JSON for comparison
-        if let Ok(content) = std::fs::read_to_string(profile_path(profile_name)) {
-            // Process JSON to hide tokens
-            if let Ok(json) = serde_json::from_str::<serde_json::Value>(&content) {
-                let mut display_json = json.clone();
-                if let Some(env_obj) = display_json.get_mut("env").and_then(|v| v.as_object_mut()) {
-                    for (key, value) in env_obj.iter_mut() {
-                        if key.contains("AUTH_TOKEN") || key.contains("TOKEN") {
-                            *value = serde_json::Value::String("••••••••••••••••".to_string());
-                        }
-                    }
-                }
+        // Show the profile JSON with tokens hidden
+        if let Ok(profile) = Profile::load_resolved(profile_name) {
+            let mut value = profile.to_value();
+            redact_settings(&mut value);
 
-                if let Ok(pretty_json) = serde_json::to_string_pretty(&display_json) {
-                    // Simulate syntax highlighting with ANSI colors
-                    let highlighted = simulate_syntax_highlighting(&pretty_json);
-                    println!("{}", highlighted);
-                }
+            if let Ok(pretty_json) = serde_json::to_string_pretty(&value) {
+                // Simulate syntax highlighting with ANSI colors
+                let highlighted = simulate_syntax_highlighting(&pretty_json);
+                println!("{}", highlighted);
             }
         }
     }
@@ -1,13 +1,19 @@
 use anyhow::{Context, Result, anyhow};
 use flate2::read::GzDecoder;
+use reqwest::blocking::Client;
 use semver::Version;
 use serde::Deserialize;
+use serde_json::json;
 use std::env;
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::PathBuf;
+use std::time::Duration;
 use tar::Archive;
 
+use crate::output::{OutputFormat, emit};
+use crate::say;
+
 const GITHUB_REPO: &str = "caibirdme/ccm";
 const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -23,6 +29,15 @@ struct Asset {
     browser_download_url: String,
 }
 
+/// Build the blocking HTTP client used for all ccm network requests
+pub(crate) fn http_client(user_agent: &str, timeout: Option<Duration>) -> Result<Client> {
+    let mut builder = Client::builder().user_agent(user_agent);
+    if let Some(timeout) = timeout {
+        builder = builder.timeout(timeout);
+    }
+    builder.build().context("Failed to create HTTP client")
+}
+
 /// Detect the current platform and return the asset name pattern
 fn detect_platform() -> Result<String> {
     let os = if cfg!(target_os = "linux") {
@@ -51,10 +66,7 @@ fn fetch_latest_release() -> Result<Release> {
         GITHUB_REPO
     );
 
-    let client = reqwest::blocking::Client::builder()
-        .user_agent("ccm-updater")
-        .build()
-        .context("Failed to create HTTP client")?;
+    let client = http_client("ccm-updater", None)?;
 
     let mut request = client.get(&url);
 
@@ -96,12 +108,9 @@ fn compare_versions(current: &str, latest: &str) -> Result<std::cmp::Ordering> {
 
 /// Download the release asset and extract the binary
 fn download_and_extract(asset_url: &str, asset_name: &str) -> Result<PathBuf> {
-    println!("📥 Downloading {}...", asset_name);
+    say!("📥 Downloading {}...", asset_name);
 
-    let client = reqwest::blocking::Client::builder()
-        .user_agent("ccm-updater")
-        .build()
-        .context("Failed to create HTTP client")?;
+    let client = http_client("ccm-updater", None)?;
 
     let response = client
         .get(asset_url)
@@ -126,7 +135,7 @@ fn download_and_extract(asset_url: &str, asset_name: &str) -> Result<PathBuf> {
 
     drop(file); // Close the file
 
-    println!("📦 Extracting binary...");
+    say!("📦 Extracting binary...");
 
     // Extract the tar.gz archive
     let tar_gz = File::open(&temp_archive).context("Failed to open downloaded archive")?;
@@ -158,7 +167,7 @@ fn download_and_extract(asset_url: &str, asset_name: &str) -> Result<PathBuf> {
 
 /// Install the new binary, replacing the current one
 fn install_binary(new_binary: &PathBuf) -> Result<()> {
-    println!("🔄 Installing update...");
+    say!("🔄 Installing update...");
 
     // Get current executable path
     let current_exe = env::current_exe().context("Failed to get current executable path")?;
@@ -194,61 +203,74 @@ fn install_binary(new_binary: &PathBuf) -> Result<()> {
         fs::remove_dir_all(extract_dir).ok();
     }
 
-    println!("✓ Update installed successfully!");
-    println!("  Backup saved to: {}", backup_path.display());
+    say!("✓ Update installed successfully!");
+    say!("  Backup saved to: {}", backup_path.display());
 
     Ok(())
 }
 
 /// Main update function
-pub fn update_self(check_only: bool) -> Result<()> {
-    println!("🔍 Checking for updates...");
-
-    let platform = detect_platform().context("Failed to detect platform")?;
+pub fn update_self(check_only: bool, format: OutputFormat) -> Result<()> {
+    say!("🔍 Checking for updates...");
 
     let release = fetch_latest_release().context("Failed to fetch latest release information")?;
 
     let latest_version = &release.tag_name;
 
-    println!("  Current version: v{}", CURRENT_VERSION);
-    println!("  Latest version:  {}", latest_version);
+    say!("  Current version: v{}", CURRENT_VERSION);
+    say!("  Latest version:  {}", latest_version);
 
-    match compare_versions(CURRENT_VERSION, latest_version)? {
+    let ordering = compare_versions(CURRENT_VERSION, latest_version)?;
+    let mut updated = false;
+    match ordering {
         std::cmp::Ordering::Less => {
-            println!("🎉 A new version is available!");
+            say!("🎉 A new version is available!");
 
             if check_only {
-                println!("\nRun 'ccm update' to install the latest version.");
-                return Ok(());
+                say!("\nRun 'ccm update' to install the latest version.");
+            } else {
+                let platform = detect_platform().context("Failed to detect platform")?;
+
+                // Find the appropriate asset for this platform
+                let asset_pattern = format!("ccm-{}-{}.tar.gz", latest_version, platform);
+                let asset = release
+                    .assets
+                    .iter()
+                    .find(|a| a.name == asset_pattern)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "No release asset found for platform: {} (expected: {})",
+                            platform,
+                            asset_pattern
+                        )
+                    })?;
+
+                let new_binary = download_and_extract(&asset.browser_download_url, &asset.name)?;
+                install_binary(&new_binary)?;
+                updated = true;
+
+                say!("\n🚀 Update complete! Please restart ccm to use the new version.");
             }
-
-            // Find the appropriate asset for this platform
-            let asset_pattern = format!("ccm-{}-{}.tar.gz", latest_version, platform);
-            let asset = release
-                .assets
-                .iter()
-                .find(|a| a.name == asset_pattern)
-                .ok_or_else(|| {
-                    anyhow!(
-                        "No release asset found for platform: {} (expected: {})",
-                        platform,
-                        asset_pattern
-                    )
-                })?;
-
-            let new_binary = download_and_extract(&asset.browser_download_url, &asset.name)?;
-            install_binary(&new_binary)?;
-
-            println!("\n🚀 Update complete! Please restart ccm to use the new version.");
         }
         std::cmp::Ordering::Equal => {
-            println!("✓ You are already running the latest version.");
+            say!("✓ You are already running the latest version.");
         }
         std::cmp::Ordering::Greater => {
-            println!("ℹ️  You are running a newer version than the latest release.");
-            println!("   This might be a development or pre-release version.");
+            say!("ℹ️  You are running a newer version than the latest release.");
+            say!("   This might be a development or pre-release version.");
         }
     }
 
-    Ok(())
+    if format.is_text() {
+        return Ok(());
+    }
+    emit(
+        format,
+        &json!({
+            "current_version": CURRENT_VERSION,
+            "latest_version": latest_version.trim_start_matches('v'),
+            "update_available": ordering == std::cmp::Ordering::Less,
+            "updated": updated,
+        }),
+    )
 }
@@ -0,0 +1,631 @@
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::BTreeSet;
+use std::fs;
+
+use crate::config::profile_path;
+use crate::interpolate::{check_placeholders, has_placeholders};
+use crate::model::{
+    API_KEY, API_TIMEOUT_MS, AUTH_TOKEN, BASE_URL, CMD_KEY, CommandSource,
+    DISABLE_NONESSENTIAL_TRAFFIC, MODEL, Profile, SMALL_FAST_MODEL, SWITCH_MODE_KEY,
+};
+use crate::output::{OutputFormat, Reported, emit};
+use crate::profile::list_profile_names;
+use crate::redact::redact_value;
+use crate::say;
+use crate::secret::is_secret_ref;
+
+/// What the value of a known key must look like
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    /// Any string (or, in `env`, a `$cmd` reference)
+    Text,
+    /// A credential: a string, `vault:` or `$cmd` reference
+    Secret,
+    /// An http(s) URL
+    Url,
+    /// A non-negative integer, as a number or a numeric string
+    Integer,
+    /// An env switch: 1 or 0, as a number or a string
+    Flag,
+    /// A JSON boolean
+    Bool,
+    /// One of a fixed set of strings
+    OneOf(&'static [&'static str]),
+    Object,
+    /// A list of strings
+    StringList,
+}
+
+/// Top-level keys of Claude Code's settings.json, plus ccm's own
+const SETTINGS: &[(&str, Kind)] = &[
+    ("$schema", Kind::Text),
+    ("alwaysThinkingEnabled", Kind::Bool),
+    ("apiKeyHelper", Kind::Text),
+    ("awsAuthRefresh", Kind::Text),
+    ("awsCredentialExport", Kind::Text),
+    ("cleanupPeriodDays", Kind::Integer),
+    ("companyAnnouncements", Kind::StringList),
+    ("disableAllHooks", Kind::Bool),
+    ("disabledMcpjsonServers", Kind::StringList),
+    ("enableAllProjectMcpServers", Kind::Bool),
+    ("enabledMcpjsonServers", Kind::StringList),
+    ("env", Kind::Object),
+    ("extends", Kind::StringList),
+    ("forceLoginMethod", Kind::OneOf(&["claudeai", "console"])),
+    ("forceLoginOrgUUID", Kind::Text),
+    ("hooks", Kind::Object),
+    ("includeCoAuthoredBy", Kind::Bool),
+    ("model", Kind::Text),
+    ("otelHeadersHelper", Kind::Text),
+    ("outputStyle", Kind::Text),
+    ("permissions", Kind::Object),
+    ("sandbox", Kind::Object),
+    ("spinnerTipsEnabled", Kind::Bool),
+    ("statusLine", Kind::Object),
+    (SWITCH_MODE_KEY, Kind::OneOf(&["replace", "merge"])),
+];
+
+/// Keys of the `permissions` object
+const PERMISSIONS: &[(&str, Kind)] = &[
+    ("additionalDirectories", Kind::StringList),
+    ("allow", Kind::StringList),
+    ("ask", Kind::StringList),
+    (
+        "defaultMode",
+        Kind::OneOf(&["default", "acceptEdits", "plan", "bypassPermissions"]),
+    ),
+    ("deny", Kind::StringList),
+    ("disableBypassPermissionsMode", Kind::OneOf(&["disable"])),
+];
+
+/// Env vars Claude Code reads
+const ENV_VARS: &[(&str, Kind)] = &[
+    (BASE_URL, Kind::Url),
+    (AUTH_TOKEN, Kind::Secret),
+    (API_KEY, Kind::Secret),
+    (MODEL, Kind::Text),
+    (SMALL_FAST_MODEL, Kind::Text),
+    ("ANTHROPIC_DEFAULT_OPUS_MODEL", Kind::Text),
+    ("ANTHROPIC_DEFAULT_SONNET_MODEL", Kind::Text),
+    ("ANTHROPIC_DEFAULT_HAIKU_MODEL", Kind::Text),
+    ("CLAUDE_CODE_SUBAGENT_MODEL", Kind::Text),
+    ("ANTHROPIC_CUSTOM_HEADERS", Kind::Secret),
+    ("ANTHROPIC_BEDROCK_BASE_URL", Kind::Url),
+    ("ANTHROPIC_VERTEX_BASE_URL", Kind::Url),
+    ("ANTHROPIC_VERTEX_PROJECT_ID", Kind::Text),
+    ("ANTHROPIC_SMALL_FAST_MODEL_AWS_REGION", Kind::Text),
+    ("AWS_BEARER_TOKEN_BEDROCK", Kind::Secret),
+    ("AWS_REGION", Kind::Text),
+    ("CLOUD_ML_REGION", Kind::Text),
+    (API_TIMEOUT_MS, Kind::Integer),
+    ("BASH_DEFAULT_TIMEOUT_MS", Kind::Integer),
+    ("BASH_MAX_TIMEOUT_MS", Kind::Integer),
+    ("BASH_MAX_OUTPUT_LENGTH", Kind::Integer),
+    ("CLAUDE_CODE_API_KEY_HELPER_TTL_MS", Kind::Integer),
+    ("CLAUDE_CODE_MAX_OUTPUT_TOKENS", Kind::Integer),
+    ("MAX_MCP_OUTPUT_TOKENS", Kind::Integer),
+    ("MAX_THINKING_TOKENS", Kind::Integer),
+    ("MCP_TIMEOUT", Kind::Integer),
+    ("MCP_TOOL_TIMEOUT", Kind::Integer),
+    (DISABLE_NONESSENTIAL_TRAFFIC, Kind::Flag),
+    ("CLAUDE_BASH_MAINTAIN_PROJECT_WORKING_DIR", Kind::Flag),
+    ("CLAUDE_CODE_DISABLE_TERMINAL_TITLE", Kind::Flag),
+    ("CLAUDE_CODE_IDE_SKIP_AUTO_INSTALL", Kind::Flag),
+    ("CLAUDE_CODE_SKIP_BEDROCK_AUTH", Kind::Flag),
+    ("CLAUDE_CODE_SKIP_VERTEX_AUTH", Kind::Flag),
+    ("CLAUDE_CODE_USE_BEDROCK", Kind::Flag),
+    ("CLAUDE_CODE_USE_VERTEX", Kind::Flag),
+    ("DISABLE_AUTOUPDATER", Kind::Flag),
+    ("DISABLE_BUG_COMMAND", Kind::Flag),
+    ("DISABLE_COST_WARNINGS", Kind::Flag),
+    ("DISABLE_ERROR_REPORTING", Kind::Flag),
+    ("DISABLE_NON_ESSENTIAL_MODEL_CALLS", Kind::Flag),
+    ("DISABLE_PROMPT_CACHING", Kind::Flag),
+    ("DISABLE_TELEMETRY", Kind::Flag),
+    ("HTTP_PROXY", Kind::Url),
+    ("HTTPS_PROXY", Kind::Url),
+    ("NO_PROXY", Kind::Text),
+    ("USE_BUILTIN_RIPGREP", Kind::Flag),
+];
+
+/// Prefixes of env vars that only Claude Code reads, so unknown ones are likely typos
+const ENV_PREFIXES: &[&str] = &["ANTHROPIC_", "CLAUDE_CODE_"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Claude Code (or ccm) cannot use the value
+    Error,
+    /// Probably a mistake, such as an unknown key
+    Warning,
+}
+
+/// A problem found in a profile, located by a JSON path like `$.env.API_TIMEOUT_MS`
+#[derive(Debug, Clone, Serialize)]
+pub struct Issue {
+    pub severity: Severity,
+    pub path: String,
+    pub message: String,
+}
+
+impl Issue {
+    fn error(path: &str, message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            path: path.to_string(),
+            message,
+        }
+    }
+
+    fn warning(path: &str, message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            path: path.to_string(),
+            message,
+        }
+    }
+}
+
+/// Append a key to a JSON path, quoting it unless it is a plain identifier
+fn child_path(parent: &str, key: &str) -> String {
+    let plain = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    if plain {
+        format!("{}.{}", parent, key)
+    } else {
+        format!("{}[{}]", parent, Value::String(key.to_string()))
+    }
+}
+
+fn lookup(registry: &[(&str, Kind)], key: &str) -> Option<Kind> {
+    registry
+        .iter()
+        .find(|(name, _)| *name == key)
+        .map(|(_, kind)| *kind)
+}
+
+/// Edit distance between two strings
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// The known key an unknown one was most likely meant to be
+fn suggest(key: &str, registry: &[(&'static str, Kind)]) -> Option<&'static str> {
+    let normalize = |s: &str| s.to_ascii_lowercase().replace(['_', '-'], "");
+    let key = normalize(key);
+    registry
+        .iter()
+        .map(|(name, _)| (distance(&key, &normalize(name)), *name))
+        .filter(|(d, _)| *d == 0 || (*d <= 2 && key.len() > 4))
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, name)| name)
+}
+
+fn did_you_mean(suggestion: Option<&str>) -> String {
+    suggestion
+        .map(|s| format!(" (did you mean {}?)", s))
+        .unwrap_or_default()
+}
+
+/// Check a value against the kind of its key. `in_env` allows `$cmd` references.
+/// Messages quote values redacted as for display, and never quote secrets.
+fn check_kind(path: &str, key: &str, kind: Kind, value: &Value, in_env: bool) -> Option<Issue> {
+    if in_env && is_secret_ref(value) {
+        return match kind {
+            Kind::Text | Kind::Secret => None,
+            _ => Some(Issue::error(
+                path,
+                "secret references are only supported for text values".to_string(),
+            )),
+        };
+    }
+    let other = redact_value(key, value);
+    let problem = match (kind, value) {
+        (Kind::Text | Kind::Secret, Value::String(_)) => None,
+        (Kind::Secret, _) => Some("expected a string".to_string()),
+        (Kind::Text, _) => Some(format!("expected a string, got {}", other)),
+        (Kind::Url, Value::String(_)) => {
+            let url = other.as_str().unwrap_or_default();
+            let rest = url
+                .strip_prefix("https://")
+                .or_else(|| url.strip_prefix("http://"));
+            match rest {
+                None => Some(format!(
+                    "expected an http:// or https:// URL, got \"{}\"",
+                    url
+                )),
+                Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+                    Some(format!("URL \"{}\" has no host", url))
+                }
+                Some(_) if url.contains(char::is_whitespace) => {
+                    Some(format!("URL \"{}\" contains whitespace", url))
+                }
+                Some(_) => None,
+            }
+        }
+        (Kind::Url, _) => Some(format!("expected a URL string, got {}", other)),
+        (Kind::Integer, Value::Number(n)) if n.is_u64() => None,
+        (Kind::Integer, Value::String(s)) if s.trim().parse::<u64>().is_ok() => None,
+        (Kind::Integer, _) => Some(format!("expected a non-negative integer, got {}", other)),
+        (Kind::Flag, Value::Number(n)) if n.as_u64().is_some_and(|n| n <= 1) => None,
+        (Kind::Flag, Value::String(s)) if matches!(s.trim(), "0" | "1") => None,
+        (Kind::Flag, _) => Some(format!("expected 1 or 0, got {}", other)),
+        (Kind::Bool, Value::Bool(_)) => None,
+        (Kind::Bool, _) => Some(format!("expected true or false, got {}", other)),
+        (Kind::OneOf(allowed), Value::String(s)) if allowed.contains(&s.as_str()) => None,
+        (Kind::OneOf(allowed), _) => Some(format!(
+            "expected one of {}, got {}",
+            allowed.join(", "),
+            other
+        )),
+        (Kind::Object, Value::Object(_)) => None,
+        (Kind::Object, _) => Some(format!("expected an object, got {}", other)),
+        (Kind::StringList, Value::Array(items)) => {
+            return items.iter().enumerate().find_map(|(i, item)| {
+                (!item.is_string()).then(|| {
+                    Issue::error(
+                        &format!("{}[{}]", path, i),
+                        format!("expected a string, got {}", redact_value(key, item)),
+                    )
+                })
+            });
+        }
+        (Kind::StringList, _) => Some(format!("expected a list of strings, got {}", other)),
+    };
+    problem.map(|message| Issue::error(path, message))
+}
+
+fn check_env(path: &str, env: &Value, issues: &mut Vec<Issue>) {
+    let Some(env) = env.as_object() else {
+        issues.extend(check_kind(path, "env", Kind::Object, env, false));
+        return;
+    };
+    for (key, value) in env {
+        let path = child_path(path, key);
+        if value.get(CMD_KEY).is_some()
+            && let Err(e) = CommandSource::from_value(key, value)
+        {
+            let message = e.to_string();
+            let prefix = format!("env.{}: ", key);
+            issues.push(Issue::error(
+                &path,
+                message
+                    .strip_prefix(&prefix)
+                    .unwrap_or(&message)
+                    .to_string(),
+            ));
+            continue;
+        }
+        let placeholders = value.as_str().filter(|_| has_placeholders(value));
+        if let Some(text) = placeholders
+            && let Err(e) = check_placeholders(text)
+        {
+            issues.push(Issue::error(&path, e.to_string()));
+        }
+        match lookup(ENV_VARS, key) {
+            // The value is only known once expanded
+            Some(_) if placeholders.is_some() => {}
+            Some(kind) => issues.extend(check_kind(&path, key, kind, value, true)),
+            None => {
+                let suggestion = suggest(key, ENV_VARS);
+                if suggestion.is_some() || ENV_PREFIXES.iter().any(|p| key.starts_with(p)) {
+                    issues.push(Issue::warning(
+                        &path,
+                        format!("unknown Claude Code env var{}", did_you_mean(suggestion)),
+                    ));
+                }
+                if !value.is_string() && !is_secret_ref(value) {
+                    issues.push(Issue::warning(
+                        &path,
+                        format!(
+                            "env values should be strings, got {}",
+                            redact_value(key, value)
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn check_object(
+    path: &str,
+    value: &Value,
+    registry: &[(&'static str, Kind)],
+    issues: &mut Vec<Issue>,
+) {
+    let Some(map) = value.as_object() else {
+        return;
+    };
+    for (key, value) in map {
+        let path = child_path(path, key);
+        match lookup(registry, key) {
+            Some(kind) => issues.extend(check_kind(&path, key, kind, value, false)),
+            None if lookup(ENV_VARS, key).is_some() => issues.push(Issue::warning(
+                &path,
+                "env var set as a setting; move it into \"env\"".to_string(),
+            )),
+            None => issues.push(Issue::warning(
+                &path,
+                format!("unknown setting{}", did_you_mean(suggest(key, registry))),
+            )),
+        }
+    }
+}
+
+/// Settings holding shell commands, whose shell expands `${VAR}` itself
+const COMMAND_SETTINGS: &[&str] = &[
+    "apiKeyHelper",
+    "awsAuthRefresh",
+    "awsCredentialExport",
+    "otelHeadersHelper",
+    "command",
+];
+
+/// Warn about `${VAR}` placeholders outside `env`, which ccm writes as-is
+fn check_literal_placeholders(path: &str, value: &Value, issues: &mut Vec<Issue>) {
+    match value {
+        Value::String(s) if s.contains("${") => issues.push(Issue::warning(
+            path,
+            "placeholders are only expanded in env; this value is written as-is".to_string(),
+        )),
+        Value::Object(map) => {
+            for (key, value) in map {
+                if !COMMAND_SETTINGS.contains(&key.as_str()) {
+                    check_literal_placeholders(&child_path(path, key), value, issues);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                check_literal_placeholders(&format!("{}[{}]", path, i), item, issues);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Check a profile's JSON against the known settings and env vars.
+/// Does not look at other profiles; see [`validate_profile`].
+pub fn validate_value(value: &Value) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let Some(map) = value.as_object() else {
+        issues.push(Issue::error(
+            "$",
+            format!("a profile must be a JSON object, got {}", value),
+        ));
+        return issues;
+    };
+    // `extends` may also be a single profile name
+    let mut rest = map.clone();
+    if matches!(map.get("extends"), Some(Value::String(_))) {
+        rest.remove("extends");
+    }
+    let env = rest.remove("env");
+    let rest = Value::Object(rest);
+    check_object("$", &rest, SETTINGS, &mut issues);
+    check_literal_placeholders("$", &rest, &mut issues);
+    if let Some(env) = env {
+        check_env("$.env", &env, &mut issues);
+    }
+    if let Some(permissions) = map.get("permissions") {
+        check_object("$.permissions", permissions, PERMISSIONS, &mut issues);
+    }
+    issues
+}
+
+/// Everything wrong with a profile's JSON, including parents that do not
+/// exist or extend it back
+pub fn profile_issues(value: &Value) -> Vec<Issue> {
+    let mut issues = validate_value(value);
+    let parents: Vec<(String, &str)> = match value.get("extends") {
+        Some(Value::String(parent)) => vec![("$.extends".to_string(), parent.as_str())],
+        Some(Value::Array(parents)) => parents
+            .iter()
+            .enumerate()
+            .filter_map(|(i, p)| p.as_str().map(|p| (format!("$.extends[{}]", i), p)))
+            .collect(),
+        _ => Vec::new(),
+    };
+    for (path, parent) in parents {
+        if !profile_path(parent).exists() {
+            issues.push(Issue::error(
+                &path,
+                format!("profile '{}' does not exist", parent),
+            ));
+        } else if let Some(cycle) = inheritance_cycle(parent, &mut Vec::new()) {
+            issues.push(Issue::error(
+                &path,
+                format!("inheritance cycle: {}", cycle.join(" -> ")),
+            ));
+        }
+    }
+    // Whatever else ccm itself cannot load
+    if error_count(&issues) == 0
+        && let Err(e) = Profile::try_from(value.clone())
+    {
+        issues.push(Issue::error("$", e.to_string()));
+    }
+    issues
+}
+
+/// The first inheritance cycle reachable from a saved profile, as the chain
+/// of names ending with the one that repeats
+fn inheritance_cycle(name: &str, chain: &mut Vec<String>) -> Option<Vec<String>> {
+    if let Some(start) = chain.iter().position(|n| n == name) {
+        let mut cycle = chain[start..].to_vec();
+        cycle.push(name.to_string());
+        return Some(cycle);
+    }
+    let profile = Profile::load(name).ok()?;
+    chain.push(name.to_string());
+    let cycle = profile
+        .extends
+        .iter()
+        .find_map(|parent| inheritance_cycle(parent, chain));
+    chain.pop();
+    cycle
+}
+
+/// Validate a saved profile, reporting unreadable JSON as an issue too
+pub fn validate_profile(name: &str) -> Result<Vec<Issue>> {
+    let p = profile_path(name);
+    if !p.exists() {
+        bail!("Profile '{}' does not exist", name);
+    }
+    let content =
+        fs::read_to_string(&p).with_context(|| format!("reading profile {}", p.display()))?;
+    Ok(match serde_json::from_str::<Value>(&content) {
+        Ok(value) => profile_issues(&value),
+        Err(e) => vec![Issue::error("$", format!("invalid JSON: {}", e))],
+    })
+}
+
+pub fn error_count(issues: &[Issue]) -> usize {
+    issues
+        .iter()
+        .filter(|i| i.severity == Severity::Error)
+        .count()
+}
+
+fn print_issues(issues: &[Issue]) {
+    for issue in issues {
+        let label = match issue.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        say!("  {}: {}: {}", label, issue.path, issue.message);
+    }
+}
+
+/// Print a profile's issues before it is saved or used, failing on errors
+pub fn check_issues(name: &str, issues: &[Issue]) -> Result<()> {
+    if issues.is_empty() {
+        return Ok(());
+    }
+    say!("⚠️  Profile '{}' has problems:", name);
+    print_issues(issues);
+    let errors = error_count(issues);
+    if errors > 0 {
+        bail!(
+            "Profile '{}' has {} error(s) (use --no-validate to skip this check)",
+            name,
+            errors
+        );
+    }
+    Ok(())
+}
+
+/// The issues of a profile and of each profile it extends
+fn chain_issues(name: &str) -> Result<Vec<(String, Vec<Issue>)>> {
+    let mut pending = vec![name.to_string()];
+    let mut seen = BTreeSet::new();
+    let mut chain = Vec::new();
+    while let Some(name) = pending.pop() {
+        if !seen.insert(name.clone()) || !profile_path(&name).exists() {
+            continue;
+        }
+        let issues = validate_profile(&name)?;
+        if let Ok(profile) = Profile::load(&name) {
+            pending.extend(profile.extends);
+        }
+        chain.push((name, issues));
+    }
+    Ok(chain)
+}
+
+/// Validate a profile and the profiles it extends before switching to it
+pub fn check_profile(name: &str) -> Result<()> {
+    for (name, issues) in chain_issues(name)? {
+        check_issues(&name, &issues)?;
+    }
+    Ok(())
+}
+
+/// The errors of a profile and the profiles it extends, one line each,
+/// for showing where printed issues would not be seen
+pub fn chain_errors(name: &str) -> Vec<String> {
+    chain_issues(name)
+        .unwrap_or_default()
+        .into_iter()
+        .flat_map(|(name, issues)| {
+            issues
+                .into_iter()
+                .filter(|i| i.severity == Severity::Error)
+                .map(move |i| format!("{}: {}: {}", name, i.path, i.message))
+        })
+        .collect()
+}
+
+/// Validate one profile, or all of them, and report every issue.
+/// Fails if any profile has errors; warnings alone do not.
+pub fn validate_profiles(name: Option<&str>, format: OutputFormat) -> Result<()> {
+    let names = match name {
+        Some(name) => vec![name.to_string()],
+        None => list_profile_names()?,
+    };
+    let mut results = Vec::new();
+    for name in names {
+        let issues = validate_profile(&name)?;
+        results.push((name, issues));
+    }
+    let invalid = results
+        .iter()
+        .filter(|(_, issues)| error_count(issues) > 0)
+        .count();
+
+    if !format.is_text() {
+        let reports: Vec<Value> = results
+            .iter()
+            .map(|(name, issues)| {
+                json!({
+                    "profile": name,
+                    "valid": error_count(issues) == 0,
+                    "issues": issues,
+                })
+            })
+            .collect();
+        emit(format, &reports)?;
+        if invalid > 0 {
+            return Err(Reported.into());
+        }
+        return Ok(());
+    }
+
+    if results.is_empty() {
+        say!("No profiles found.");
+        return Ok(());
+    }
+    for (name, issues) in &results {
+        if issues.is_empty() {
+            say!("✓ {}", name);
+        } else {
+            let marker = if error_count(issues) > 0 {
+                "✗"
+            } else {
+                "⚠️ "
+            };
+            say!("{} {}", marker, name);
+            print_issues(issues);
+        }
+    }
+    if invalid > 0 {
+        bail!("{} of {} profile(s) have errors", invalid, results.len());
+    }
+    Ok(())
+}
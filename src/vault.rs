@@ -0,0 +1,378 @@
+use anyhow::{Context, Result, anyhow, bail};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rpassword::read_password;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::config::{ensure_ccm_dir, vault_path};
+use crate::model::{API_KEY, AUTH_TOKEN, Profile, ProfileEnv};
+use crate::output::{OutputFormat, emit};
+use crate::profile::list_profile_names;
+use crate::say;
+use crate::secret::{VAULT_PREFIX, needs_resolving};
+
+/// Environment variable that supplies the vault passphrase non-interactively
+const PASSPHRASE_ENV: &str = "CCM_VAULT_PASSPHRASE";
+const VAULT_VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+
+/// Key derivation parameters, stored alongside the ciphertext
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KdfParams {
+    algorithm: String,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    salt: String,
+}
+
+/// On-disk vault layout: the secrets map is encrypted as a single JSON blob
+#[derive(Serialize, Deserialize)]
+struct VaultFile {
+    version: u32,
+    kdf: KdfParams,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Passphrase-protected store of named secrets (XChaCha20-Poly1305, Argon2id key)
+pub struct Vault {
+    secrets: BTreeMap<String, String>,
+    kdf: KdfParams,
+    key: Key,
+}
+
+/// Whether CCM_VAULT_PASSPHRASE is set, so the vault can be opened without a prompt
+pub fn has_env_passphrase() -> bool {
+    env::var_os(PASSPHRASE_ENV).is_some()
+}
+
+/// Read the vault passphrase from CCM_VAULT_PASSPHRASE or prompt for it.
+/// The prompt goes to stderr so it never ends up in captured output.
+fn read_passphrase(prompt: &str) -> Result<String> {
+    if let Ok(passphrase) = env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    eprint!("{}", prompt);
+    io::stderr().flush()?;
+    Ok(read_password()?)
+}
+
+fn derive_key(passphrase: &str, kdf: &KdfParams) -> Result<Key> {
+    if kdf.algorithm != "argon2id" {
+        bail!("Unsupported vault key derivation '{}'", kdf.algorithm);
+    }
+    let salt = STANDARD.decode(&kdf.salt).context("decoding vault salt")?;
+    let params = Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, Some(32))
+        .map_err(|e| anyhow!("invalid vault KDF parameters: {}", e))?;
+    let mut key = Key::default();
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+        .map_err(|e| anyhow!("deriving vault key: {}", e))?;
+    Ok(key)
+}
+
+impl Vault {
+    /// Open the vault, creating a new one (with a new passphrase) if none exists
+    pub fn open() -> Result<Self> {
+        if vault_path().exists() {
+            Self::open_existing()
+        } else {
+            Self::create()
+        }
+    }
+
+    /// Open an existing vault, failing if it has not been created yet
+    pub fn open_existing() -> Result<Self> {
+        let path = vault_path();
+        if !path.exists() {
+            bail!(
+                "No secret vault found at {}.\n\
+                Store a secret first using: ccm secret set <name>",
+                path.display()
+            );
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("reading vault {}", path.display()))?;
+        let file: VaultFile = serde_json::from_str(&content)
+            .with_context(|| format!("parsing vault {}", path.display()))?;
+        if file.version != VAULT_VERSION {
+            bail!("Unsupported vault version {}", file.version);
+        }
+
+        let passphrase = read_passphrase("Vault passphrase: ")?;
+        let key = derive_key(&passphrase, &file.kdf)?;
+        let nonce = STANDARD
+            .decode(&file.nonce)
+            .context("decoding vault nonce")?;
+        let ciphertext = STANDARD
+            .decode(&file.ciphertext)
+            .context("decoding vault ciphertext")?;
+        if nonce.len() != 24 {
+            bail!("Corrupted vault: invalid nonce length");
+        }
+        let plaintext = XChaCha20Poly1305::new(&key)
+            .decrypt(XNonce::from_slice(&nonce), ciphertext.as_ref())
+            .map_err(|_| anyhow!("Failed to unlock vault: wrong passphrase or corrupted file"))?;
+        let secrets =
+            serde_json::from_slice(&plaintext).context("parsing decrypted vault contents")?;
+
+        Ok(Self {
+            secrets,
+            kdf: file.kdf,
+            key,
+        })
+    }
+
+    fn create() -> Result<Self> {
+        say!("Creating a new secret vault at {}", vault_path().display());
+        let passphrase = read_passphrase("New vault passphrase: ")?;
+        if passphrase.is_empty() {
+            bail!("Vault passphrase must not be empty");
+        }
+        if env::var(PASSPHRASE_ENV).is_err()
+            && read_passphrase("Confirm vault passphrase: ")? != passphrase
+        {
+            bail!("Passphrases do not match");
+        }
+
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let defaults = Params::default();
+        let kdf = KdfParams {
+            algorithm: "argon2id".to_string(),
+            m_cost: defaults.m_cost(),
+            t_cost: defaults.t_cost(),
+            p_cost: defaults.p_cost(),
+            salt: STANDARD.encode(salt),
+        };
+        let key = derive_key(&passphrase, &kdf)?;
+        Ok(Self {
+            secrets: BTreeMap::new(),
+            kdf,
+            key,
+        })
+    }
+
+    /// Encrypt and write the vault, using a fresh nonce on every save
+    pub fn save(&self) -> Result<()> {
+        ensure_ccm_dir()?;
+        let plaintext = serde_json::to_vec(&self.secrets)?;
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = XChaCha20Poly1305::new(&self.key)
+            .encrypt(&nonce, plaintext.as_ref())
+            .map_err(|_| anyhow!("encrypting vault"))?;
+        let file = VaultFile {
+            version: VAULT_VERSION,
+            kdf: self.kdf.clone(),
+            nonce: STANDARD.encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+        };
+
+        let path = vault_path();
+        write_private(&path, serde_json::to_string_pretty(&file)?.as_bytes())
+            .with_context(|| format!("writing vault {}", path.display()))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.secrets.get(name).map(|s| s.as_str())
+    }
+
+    pub fn set(&mut self, name: &str, value: String) {
+        self.secrets.insert(name.to_string(), value);
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.secrets.remove(name).is_some()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.secrets.keys().map(|s| s.as_str())
+    }
+}
+
+/// Write a file readable by the owner only. The contents go to a temporary
+/// file created with those permissions, which then replaces the target, so
+/// the file is never briefly readable by others nor left half-written.
+fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    let tmp_path = path.with_extension("json.tmp");
+    // A leftover file would keep its old permissions
+    if tmp_path.exists() {
+        fs::remove_file(&tmp_path)?;
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut tmp = options.open(&tmp_path)?;
+    tmp.write_all(contents)?;
+    tmp.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+fn validate_secret_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.chars().any(char::is_whitespace) {
+        bail!(
+            "Invalid secret name '{}': must be non-empty without spaces",
+            name
+        );
+    }
+    Ok(())
+}
+
+/// Store a secret, reading the value from a hidden prompt or from stdin
+pub fn set_secret(name: &str, from_stdin: bool, format: OutputFormat) -> Result<()> {
+    validate_secret_name(name)?;
+    let value = if from_stdin {
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        input.trim().to_string()
+    } else {
+        eprint!("Value for secret '{}': ", name);
+        io::stderr().flush()?;
+        read_password()?.trim().to_string()
+    };
+    if value.is_empty() {
+        bail!("Secret value must not be empty");
+    }
+
+    let mut vault = Vault::open()?;
+    vault.set(name, value);
+    vault.save()?;
+    say!(
+        "✓ Stored secret '{}'. Reference it in a profile as \"{}{}\"",
+        name,
+        VAULT_PREFIX,
+        name
+    );
+    if !format.is_text() {
+        return emit(
+            format,
+            &json!({ "name": name, "reference": format!("{}{}", VAULT_PREFIX, name) }),
+        );
+    }
+    Ok(())
+}
+
+/// Print a secret's value
+pub fn get_secret(name: &str, format: OutputFormat) -> Result<()> {
+    let vault = Vault::open_existing()?;
+    let value = vault
+        .get(name)
+        .ok_or_else(|| anyhow!("Secret '{}' does not exist", name))?;
+    if !format.is_text() {
+        return emit(format, &json!({ "name": name, "value": value }));
+    }
+    println!("{}", value);
+    Ok(())
+}
+
+/// Remove a secret from the vault
+pub fn remove_secret(name: &str, format: OutputFormat) -> Result<()> {
+    let mut vault = Vault::open_existing()?;
+    let removed = vault.remove(name);
+    if removed {
+        vault.save()?;
+        say!("Removed secret '{}'", name);
+    } else {
+        say!("Secret '{}' does not exist", name);
+    }
+    if !format.is_text() {
+        return emit(format, &json!({ "name": name, "removed": removed }));
+    }
+    Ok(())
+}
+
+/// List the names of stored secrets (never their values)
+pub fn list_secrets(format: OutputFormat) -> Result<()> {
+    let vault = Vault::open_existing()?;
+    if !format.is_text() {
+        let names: Vec<&str> = vault.names().collect();
+        return emit(format, &json!({ "vault": vault_path(), "secrets": names }));
+    }
+    say!("Secrets in {}:", vault_path().display());
+    for name in vault.names() {
+        say!(" - {}", name);
+    }
+    Ok(())
+}
+
+/// Move plaintext tokens out of every profile into the vault.
+/// `ANTHROPIC_AUTH_TOKEN` is stored as `<profile>`, `ANTHROPIC_API_KEY` as `<profile>-api-key`.
+pub fn migrate_profile_tokens(format: OutputFormat) -> Result<()> {
+    let mut vault = Vault::open()?;
+    let mut migrated = Vec::new();
+
+    for name in list_profile_names()? {
+        let mut profile = Profile::load(&name)?;
+        let mut env = profile.env.to_map();
+        let mut changed = false;
+
+        for (key, secret_name) in [
+            (AUTH_TOKEN, name.clone()),
+            (API_KEY, format!("{}-api-key", name)),
+        ] {
+            let Some(value) = env.get(key) else {
+                continue;
+            };
+            // References, commands and placeholders are resolved when applied
+            if needs_resolving(value) {
+                continue;
+            }
+            let Some(token) = value.as_str().filter(|t| !t.is_empty()) else {
+                continue;
+            };
+            if let Some(existing) = vault.get(&secret_name)
+                && existing != token
+            {
+                say!(
+                    "⚠️  Skipping {} of '{}': secret '{}' already exists with a different value",
+                    key,
+                    name,
+                    secret_name
+                );
+                continue;
+            }
+            vault.set(&secret_name, token.to_string());
+            env.insert(
+                key.to_string(),
+                Value::String(format!("{}{}", VAULT_PREFIX, secret_name)),
+            );
+            changed = true;
+        }
+
+        if changed {
+            profile.env = ProfileEnv::from_map(env);
+            migrated.push((name, profile));
+        }
+    }
+
+    if migrated.is_empty() {
+        say!("No plaintext tokens found in profiles");
+    } else {
+        // Save the vault first so a failure never leaves profiles pointing at missing secrets
+        vault.save()?;
+        for (name, profile) in &migrated {
+            profile.save(name)?;
+            say!("✓ Moved tokens of profile '{}' into the vault", name);
+        }
+    }
+    if !format.is_text() {
+        let names: Vec<&str> = migrated.iter().map(|(name, _)| name.as_str()).collect();
+        return emit(format, &json!({ "migrated": names }));
+    }
+    Ok(())
+}
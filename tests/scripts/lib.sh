@@ -0,0 +1,68 @@
+#!/bin/bash
+# Shared fixture for the integration test scripts. Source it with the name of
+# the test, which is used for the temporary directory:
+#
+#   source "$(dirname "${BASH_SOURCE[0]}")/lib.sh" vault
+#
+# It builds ccm, points it at a fresh config directory and settings file that
+# are removed on exit, and defines the check helpers.
+
+set -e
+
+TEST_DIR="/tmp/ccm-$1-test-$$"
+export CCM_CONFIG_DIR="$TEST_DIR/ccm"
+export CLAUDE_SETTINGS_PATH="$TEST_DIR/claude/settings.json"
+
+echo "Setting up test environment in: $TEST_DIR"
+mkdir -p "$TEST_DIR/ccm/profiles"
+mkdir -p "$TEST_DIR/claude"
+trap 'rm -rf "$TEST_DIR"' EXIT
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+PROJECT_DIR="$(cd "$SCRIPT_DIR/../.." && pwd)"
+cd "$PROJECT_DIR"
+cargo build --quiet
+
+CCM="$PROJECT_DIR/target/debug/ccm"
+PROFILES="$CCM_CONFIG_DIR/profiles"
+
+FAILED=0
+
+# Pass if the command succeeds: check <description> <command...>
+check() {
+    local description=$1
+    shift
+    if "$@"; then
+        echo "✓ $description"
+    else
+        echo "✗ $description"
+        FAILED=1
+    fi
+}
+
+# Pass if the command fails: check_not <description> <command...>
+check_not() {
+    local description=$1
+    shift
+    if "$@"; then
+        echo "✗ $description"
+        FAILED=1
+    else
+        echo "✓ $description"
+    fi
+}
+
+# Print an env value from the Claude settings file
+setting() {
+    python3 -c "import json,sys; print(json.load(open(sys.argv[1]))['env'][sys.argv[2]])" \
+        "$CLAUDE_SETTINGS_PATH" "$1"
+}
+
+# Exit with the overall result: finish <what was tested>
+finish() {
+    if [ $FAILED -ne 0 ]; then
+        exit 1
+    fi
+    echo ""
+    echo "All $1 tests passed!"
+}
@@ -0,0 +1,101 @@
+#!/bin/bash
+# Test script for `ccm test` against a local mock Anthropic endpoint
+
+source "$(dirname "${BASH_SOURCE[0]}")/lib.sh" health
+
+# Mock /v1/messages: accepts token "sk-good" and models starting with "good-"
+cat > "$TEST_DIR/server.py" << 'EOF'
+import json, sys
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+class Handler(BaseHTTPRequestHandler):
+    def do_POST(self):
+        body = json.loads(self.rfile.read(int(self.headers["Content-Length"])))
+        if self.path != "/v1/messages":
+            self.reply(404, {"error": {"message": "not found"}})
+        elif self.headers.get("Authorization") != "Bearer sk-good":
+            self.reply(401, {"error": {"message": "invalid x-api-key"}})
+        elif not body["model"].startswith("good-"):
+            self.reply(404, {"error": {"message": "model: " + body["model"]}})
+        else:
+            self.reply(200, {"model": body["model"], "content": []})
+
+    def reply(self, code, payload):
+        data = json.dumps(payload).encode()
+        self.send_response(code)
+        self.send_header("Content-Type", "application/json")
+        self.send_header("Content-Length", str(len(data)))
+        self.end_headers()
+        self.wfile.write(data)
+
+    def log_message(self, *args):
+        pass
+
+server = HTTPServer(("127.0.0.1", 0), Handler)
+print(server.server_port, flush=True)
+server.serve_forever()
+EOF
+
+python3 "$TEST_DIR/server.py" > "$TEST_DIR/port" &
+SERVER_PID=$!
+cleanup() {
+    kill $SERVER_PID 2>/dev/null || true
+    rm -rf "$TEST_DIR"
+}
+trap cleanup EXIT
+
+for _ in $(seq 50); do
+    [ -s "$TEST_DIR/port" ] && break
+    sleep 0.1
+done
+BASE_URL="http://127.0.0.1:$(cat "$TEST_DIR/port")"
+
+write_profile() {
+    cat > "$CCM_CONFIG_DIR/profiles/$1.json" << EOF
+{
+  "env": {
+    "ANTHROPIC_BASE_URL": "$2",
+    "ANTHROPIC_AUTH_TOKEN": "$3",
+    "ANTHROPIC_MODEL": "$4",
+    "ANTHROPIC_SMALL_FAST_MODEL": "good-small",
+    "API_TIMEOUT_MS": 2000
+  }
+}
+EOF
+}
+
+write_profile ok "$BASE_URL/" sk-good good-large
+write_profile badtoken "$BASE_URL" sk-bad good-large
+write_profile badmodel "$BASE_URL" sk-good bad-large
+write_profile down "http://127.0.0.1:1" sk-good good-large
+
+expect() {
+    local name=$1 status=$2 pattern=$3
+    local output code=0
+    output=$($CCM test "$name" 2>&1) || code=$?
+    if { [ "$status" = ok ] && [ $code -ne 0 ]; } || { [ "$status" = fail ] && [ $code -eq 0 ]; }; then
+        echo "✗ ccm test $name: unexpected exit code $code"
+        echo "$output"
+        FAILED=1
+    elif ! grep -q "$pattern" <<< "$output"; then
+        echo "✗ ccm test $name: output does not mention '$pattern'"
+        echo "$output"
+        FAILED=1
+    else
+        echo "✓ ccm test $name"
+    fi
+}
+
+echo ""
+echo "=== Testing profiles against mock endpoint ==="
+expect ok ok "is working"
+expect badtoken fail "authentication failed"
+expect badmodel fail "model not accepted"
+expect down fail "endpoint unreachable"
+
+output=$($CCM test badtoken --output json 2> /dev/null) && code=0 || code=$?
+check "a failed check exits with code 1 in JSON mode" [ $code -eq 1 ]
+check "the JSON result is the only document printed" \
+    eval 'python3 -c "import json,sys; assert not json.load(sys.stdin)[\"passed\"]" <<< "$output"'
+
+finish "health check"
@@ -0,0 +1,63 @@
+#!/bin/bash
+# Test script for ${VAR} placeholders in profile env values
+
+source "$(dirname "${BASH_SOURCE[0]}")/lib.sh" interpolation
+
+cat > "$PROFILES/glm.json" << 'EOF'
+{
+  "env": {
+    "ANTHROPIC_BASE_URL": "${GLM_URL:-https://api.example.com}",
+    "ANTHROPIC_AUTH_TOKEN": "${env:GLM_TOKEN}",
+    "API_TIMEOUT_MS": "${GLM_TIMEOUT:-600000}",
+    "NOTE": "costs $${CENTS}"
+  }
+}
+EOF
+unset GLM_URL GLM_TOKEN GLM_TIMEOUT
+
+echo ""
+echo "=== Testing expansion on switch ==="
+output=$($CCM switch glm 2>&1) && code=0 || code=$?
+check "switch fails when a required variable is unset" [ $code -ne 0 ]
+check "error names the variable" grep -q "GLM_TOKEN is not set" <<< "$output"
+check "nothing was written" [ ! -f "$CLAUDE_SETTINGS_PATH" ]
+
+export GLM_TOKEN=sk-from-env
+$CCM switch glm > /dev/null
+check "token comes from the environment" [ "$(setting ANTHROPIC_AUTH_TOKEN)" = sk-from-env ]
+check "default is used for an unset variable" [ "$(setting ANTHROPIC_BASE_URL)" = https://api.example.com ]
+check "integer settings can be placeholders" [ "$(setting API_TIMEOUT_MS)" = 600000 ]
+check "\$\${ is a literal \${" [ "$(setting NOTE)" = 'costs ${CENTS}' ]
+check "profile keeps its placeholders" grep -q '${env:GLM_TOKEN}' "$PROFILES/glm.json"
+
+echo ""
+echo "=== Testing show, diff and sync ==="
+output=$($CCM show glm --resolved)
+check "show --resolved masks the expanded token" grep -q '"ANTHROPIC_AUTH_TOKEN": "sk-\*\*\*\*\*\*\*\*"' <<< "$output"
+check "show --resolved prints other values expanded" grep -q '"API_TIMEOUT_MS": "600000"' <<< "$output"
+check "diff sees no changes" grep -q "matches" <<< "$($CCM diff glm)"
+$CCM sync < /dev/null > /dev/null
+check "sync does not copy expanded values into the profile" grep -q '${GLM_URL:-https://api.example.com}' "$PROFILES/glm.json"
+
+GLM_URL=https://other.example.com $CCM switch glm > /dev/null
+check "variables override defaults" [ "$(setting ANTHROPIC_BASE_URL)" = https://other.example.com ]
+
+echo ""
+echo "=== Testing edits and placeholders outside env ==="
+check "diff allows variables that changed since the switch" grep -q "matches" <<< "$($CCM diff glm)"
+sed -i 's|costs |price |' "$CLAUDE_SETTINGS_PATH"
+check "diff shows an edited expanded value" grep -q "price" <<< "$($CCM diff glm)"
+
+cat > "$PROFILES/dirs.json" << 'EOF'
+{
+  "env": { "ANTHROPIC_BASE_URL": "https://api.example.com" },
+  "permissions": { "additionalDirectories": ["${HOME}/shared"] },
+  "statusLine": { "type": "command", "command": "echo ${USER}" }
+}
+EOF
+output=$($CCM validate dirs 2>&1)
+check "validate warns about placeholders outside env" \
+    grep -q 'additionalDirectories\[0\]: placeholders are only expanded in env' <<< "$output"
+check "validate accepts placeholders in commands" [ -z "$(grep statusLine <<< "$output")" ]
+
+finish "interpolation"
@@ -0,0 +1,61 @@
+#!/bin/bash
+# Test script for profile metadata: ccm describe, ls --long/--tag/--sort
+
+source "$(dirname "${BASH_SOURCE[0]}")/lib.sh" metadata
+
+names() {
+    "$CCM" ls "$@" | sed -n 's/^ - \([^ ]*\).*/\1/p' | tr '\n' ' '
+}
+
+export TOKEN=sk-test
+for name in alpha beta gamma; do
+    $CCM add $name --base-url https://$name.example.com --token-env TOKEN --no-input > /dev/null
+done
+
+echo ""
+echo "=== Testing ccm describe ==="
+$CCM describe beta "Cheap endpoint" --tag cheap --tag cn --provider acme > /dev/null
+$CCM describe gamma --tag cheap > /dev/null
+check "metadata is kept next to the profile" [ -f "$PROFILES/.beta.meta.json" ]
+check "metadata is not part of the profile" [ -z "$(grep "Cheap endpoint" "$PROFILES/beta.json")" ]
+output=$($CCM describe beta)
+check "describe prints the description" grep -q "Cheap endpoint" <<< "$output"
+check "describe prints the tags" grep -q "cheap, cn" <<< "$output"
+$CCM describe beta --untag cn > /dev/null
+check "untag removes a tag" grep -q '"tags": \[ *"cheap" *\]' <<< "$($CCM describe beta --output json | tr -d '\n')"
+
+echo ""
+echo "=== Testing switch counts and ls ==="
+$CCM switch beta > /dev/null
+sleep 1
+$CCM switch gamma < /dev/null > /dev/null
+$CCM switch gamma < /dev/null > /dev/null
+check "switches are counted" grep -q '"switch_count": 2' <<< "$($CCM describe gamma --output json)"
+check "ls keeps profiles ordered by name" [ "$(names)" = "alpha beta gamma " ]
+check "ls --tag filters by tag" [ "$(names --tag cheap)" = "beta gamma " ]
+check "ls --sort last-used puts the latest first" [ "$(names --sort last-used)" = "gamma beta alpha " ]
+check "ls --sort most-used puts the most switched first" [ "$(names --sort most-used)" = "gamma beta alpha " ]
+check "ls --long shows descriptions" grep -q "Cheap endpoint" <<< "$($CCM ls --long)"
+check "ls --long shows switch counts" grep -q "(2 switches)" <<< "$($CCM ls --long)"
+
+created=$(grep created_at "$PROFILES/.gamma.meta.json")
+$CCM add gamma --base-url https://new.example.com --token-env TOKEN --no-input --force > /dev/null
+check "add --force keeps the switch count" grep -q '"switch_count": 2' <<< "$($CCM describe gamma --output json)"
+check "add --force keeps the creation time" grep -qF "$created" "$PROFILES/.gamma.meta.json"
+
+cp "$PROFILES/.gamma.meta.json" "$TEST_DIR/gamma.meta.json"
+echo "{ not json" > "$PROFILES/.gamma.meta.json"
+output=$($CCM ls 2> "$TEST_DIR/stderr")
+check "ls lists profiles despite broken metadata" [ "$(sed -n 's/^ - \([^ ]*\).*/\1/p' <<< "$output" | tr '\n' ' ')" = "alpha beta gamma " ]
+check "ls warns about broken metadata" grep -q "Ignoring metadata of profile 'gamma'" "$TEST_DIR/stderr"
+cp "$TEST_DIR/gamma.meta.json" "$PROFILES/.gamma.meta.json"
+
+echo ""
+echo "=== Testing rename and remove ==="
+$CCM rename beta delta > /dev/null
+check "rename moves the metadata" grep -q "Cheap endpoint" <<< "$($CCM describe delta)"
+check "rename leaves no metadata behind" [ ! -f "$PROFILES/.beta.meta.json" ]
+$CCM rm delta > /dev/null
+check "remove deletes the metadata" [ ! -f "$PROFILES/.delta.meta.json" ]
+
+finish "metadata"
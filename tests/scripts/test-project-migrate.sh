@@ -0,0 +1,58 @@
+#!/bin/bash
+# Test script for re-keying project mappings written by older versions
+
+source "$(dirname "${BASH_SOURCE[0]}")/lib.sh" project-migrate
+
+MAPPINGS="$CCM_CONFIG_DIR/projects"
+mkdir -p "$TEST_DIR/work/real/.claude" "$MAPPINGS"
+ln -s "$TEST_DIR/work/real" "$TEST_DIR/work/link"
+REAL="$(cd "$TEST_DIR/work/real" && pwd -P)"
+
+for name in old new; do
+    cat > "$PROFILES/$name.json" << EOF2
+{ "env": { "ANTHROPIC_MODEL": "$name-model" } }
+EOF2
+done
+
+# FNV-1a (64-bit) of a path, as the mapping files are named
+fnv1a64() {
+    python3 -c '
+import sys
+h = 0xcbf29ce484222325
+for b in sys.argv[1].encode():
+    h = ((h ^ b) * 0x100000001b3) % 2**64
+print(f"{h:016x}")' "$1"
+}
+
+# Old-style files: keyed by another hash, the newer one through the symlink
+cat > "$MAPPINGS/0123456789abcdef.json" << EOF2
+{ "profile": "old", "path": "$TEST_DIR/work/real" }
+EOF2
+cat > "$MAPPINGS/fedcba9876543210.json" << EOF2
+{ "profile": "new", "path": "$TEST_DIR/work/link" }
+EOF2
+touch -d "1 hour ago" "$MAPPINGS/0123456789abcdef.json"
+
+echo ""
+echo "=== Testing migration of old mapping files ==="
+output=$($CCM projects ls 2>&1)
+TARGET="$MAPPINGS/$(fnv1a64 "$REAL").json"
+check "the mapping is re-keyed by the canonical path" [ -f "$TARGET" ]
+check "the old files are removed" \
+    [ ! -e "$MAPPINGS/0123456789abcdef.json" -a ! -e "$MAPPINGS/fedcba9876543210.json" ]
+check "the migration marker is written" [ -f "$MAPPINGS/.hash-fnv1a64" ]
+check "the symlink collision is reported" grep -q "keeping the newer" <<< "$output"
+check "the newer mapping wins" grep -q '"profile": "new"' "$TARGET"
+check "the stored path is canonical" grep -q "\"path\": \"$REAL\"" "$TARGET"
+check "the project resolves to the migrated mapping" \
+    grep -q "real → new" <<< "$($CCM projects ls 2>&1)"
+
+echo ""
+echo "=== Testing the migration runs once ==="
+cat > "$MAPPINGS/00000000000000ff.json" << EOF2
+{ "profile": "old", "path": "$TEST_DIR/work/real" }
+EOF2
+$CCM projects ls > /dev/null 2>&1
+check "files written after the marker are left alone" [ -f "$MAPPINGS/00000000000000ff.json" ]
+
+finish "project migration"
@@ -0,0 +1,67 @@
+#!/bin/bash
+# Test script for `ccm projects`
+
+source "$(dirname "${BASH_SOURCE[0]}")/lib.sh" projects
+
+mkdir -p "$TEST_DIR/work/alpha/.claude" "$TEST_DIR/work/beta/.claude"
+
+cat > "$CCM_CONFIG_DIR/profiles/foo.json" << 'EOF'
+{
+  "env": {
+    "ANTHROPIC_BASE_URL": "https://api.foo.com"
+  }
+}
+EOF
+
+for project in alpha beta; do
+    (cd "$TEST_DIR/work/$project" && $CCM switch foo -p < /dev/null > /dev/null)
+done
+
+echo ""
+echo "=== Testing projects clear for removed directories ==="
+rm -rf "$TEST_DIR/work/alpha"
+cd "$TEST_DIR/work"
+output=$($CCM projects clear alpha 2>&1)
+check "a relative path finds the recorded mapping" grep -q "Removed project mapping" <<< "$output"
+check "the mapping is gone" [ -z "$($CCM projects ls | grep alpha)" ]
+check "other mappings are kept" grep -q beta <<< "$($CCM projects ls)"
+output=$($CCM projects clear "$TEST_DIR/work/gone" 2>&1) && code=0 || code=$?
+check "clearing an unknown directory fails" [ $code -ne 0 ]
+check "the failure names the directory" grep -q "No project profile is set" <<< "$output"
+cd "$PROJECT_DIR"
+
+echo ""
+echo "=== Testing projects ls and prune with a broken mapping ==="
+mkdir -p "$TEST_DIR/work/gamma/.claude"
+(cd "$TEST_DIR/work/gamma" && $CCM switch foo -p < /dev/null > /dev/null)
+echo "{ not json" > "$TEST_DIR/work/gamma/.claude/settings.local.json"
+rm -rf "$TEST_DIR/work/beta"
+output=$($CCM projects ls 2>&1) && code=0 || code=$?
+check "ls succeeds despite the broken mapping" [ $code -eq 0 ]
+check "ls reports the broken mapping as an error" grep -q "gamma → foo (error:" <<< "$output"
+check "ls still lists the other mappings" grep -q "beta → foo (directory missing)" <<< "$output"
+check "ls --output json reports an error status" \
+    grep -q '"status": "error"' <<< "$($CCM projects ls --output json)"
+output=$($CCM projects prune 2>&1) && code=0 || code=$?
+check "prune succeeds despite the broken mapping" [ $code -eq 0 ]
+check "prune removes stale mappings" [ -z "$($CCM projects ls | grep beta)" ]
+check "prune keeps the broken mapping" grep -q gamma <<< "$($CCM projects ls)"
+
+echo ""
+echo "=== Testing migration of colliding legacy mappings ==="
+export CCM_CONFIG_DIR="$TEST_DIR/legacy"
+mkdir -p "$CCM_CONFIG_DIR/projects" "$TEST_DIR/work/delta"
+ln -s "$TEST_DIR/work/delta" "$TEST_DIR/work/delta-link"
+for old in newer older; do
+    cat > "$CCM_CONFIG_DIR/projects/$old.json" << EOF
+{ "profile": "$old", "path": "$TEST_DIR/work/delta" }
+EOF
+done
+sed -i 's|work/delta"|work/delta-link"|' "$CCM_CONFIG_DIR/projects/older.json"
+touch -d '2 days ago' "$CCM_CONFIG_DIR/projects/older.json"
+output=$($CCM projects ls 2>&1)
+check "the newer mapping wins" grep -q "delta → newer" <<< "$output"
+check "only one mapping is kept" [ "$(grep -c delta <<< "$($CCM projects ls 2> /dev/null)")" -eq 1 ]
+check "the dropped mapping is reported" grep -q "keeping the newer" <<< "$output"
+
+finish "projects"
@@ -0,0 +1,105 @@
+#!/bin/bash
+# Test script for `ccm env` quoting and the shell hook
+
+source "$(dirname "${BASH_SOURCE[0]}")/lib.sh" shell
+
+mkdir -p "$TEST_DIR/work/alpha/.claude" "$TEST_DIR/work/beta/.claude"
+
+cat > "$PROFILES/foo.json" << 'EOF2'
+{
+  "env": {
+    "ANTHROPIC_BASE_URL": "https://api.foo.com",
+    "ANTHROPIC_MODEL": "foo-model"
+  }
+}
+EOF2
+
+cat > "$PROFILES/bar.json" << 'EOF2'
+{
+  "env": {
+    "ANTHROPIC_BASE_URL": "https://api.bar.com"
+  }
+}
+EOF2
+
+(cd "$TEST_DIR/work/alpha" && $CCM switch foo -p < /dev/null > /dev/null)
+(cd "$TEST_DIR/work/beta" && $CCM switch bar -p < /dev/null > /dev/null)
+
+# Values the shells would mangle without quoting, one per variable;
+# `$${` is ccm's escape for a literal `${`
+python3 - "$PROFILES/tricky.json" "$TEST_DIR/expected" << 'PY'
+import json, os, sys
+values = {
+    "ANTHROPIC_MODEL": "it's quoted 'twice'",
+    "ANTHROPIC_SMALL_FAST_MODEL": "back\\slash \\' and \\\\n",
+    "CCM_TEST_DOLLAR": "$HOME $${HOME} $(echo no) `echo no`",
+    "CCM_TEST_NEWLINES": "first line\nsecond line\n\nlast",
+    "CCM_TEST_SPACES": "  leading, inner  and trailing  ",
+}
+json.dump({"env": values}, open(sys.argv[1], "w"))
+os.mkdir(sys.argv[2])
+for key, value in values.items():
+    open(os.path.join(sys.argv[2], key), "w").write(value.replace("$${", "${"))
+PY
+KEYS="ANTHROPIC_MODEL ANTHROPIC_SMALL_FAST_MODEL CCM_TEST_DOLLAR CCM_TEST_NEWLINES CCM_TEST_SPACES"
+
+# Check the values a shell wrote to <dir> match the profile: round_trip <dir>
+round_trip() {
+    for key in $KEYS; do
+        if ! cmp -s "$TEST_DIR/expected/$key" "$1/$key"; then
+            echo "  $key differs"
+            return 1
+        fi
+    done
+}
+
+echo ""
+echo "=== Testing ccm env quoting ==="
+mkdir "$TEST_DIR/bash"
+(
+    eval "$($CCM env tricky --shell bash)"
+    for key in $KEYS; do printf '%s' "${!key}" > "$TEST_DIR/bash/$key"; done
+)
+check "bash reads back every value" round_trip "$TEST_DIR/bash"
+
+if command -v fish > /dev/null; then
+    mkdir "$TEST_DIR/fish"
+    $CCM env tricky --shell fish | KEYS="$KEYS" OUT="$TEST_DIR/fish" fish -c '
+        source
+        for key in (string split " " $KEYS)
+            printf "%s" $$key > $OUT/$key
+        end'
+    check "fish reads back every value" round_trip "$TEST_DIR/fish"
+else
+    echo "- fish is not installed, skipping its round trip"
+fi
+
+echo ""
+echo "=== Testing the bash hook restores prior values ==="
+# Run the hook as the prompt would after each cd, then print the variables
+output=$(
+    export ANTHROPIC_BASE_URL="https://mine.example.com"
+    unset ANTHROPIC_MODEL
+    hook() { eval "$($CCM hook-env --shell bash)"; }
+    cd "$TEST_DIR/work/alpha" && hook
+    echo "alpha: $ANTHROPIC_BASE_URL ${ANTHROPIC_MODEL-unset}"
+    cd "$TEST_DIR/work/beta" && hook
+    echo "beta: $ANTHROPIC_BASE_URL ${ANTHROPIC_MODEL-unset}"
+    cd "$TEST_DIR/work" && hook
+    echo "outside: $ANTHROPIC_BASE_URL ${ANTHROPIC_MODEL-unset} ${CCM_HOOK_SAVED-unset}"
+)
+check "entering a project exports its env" \
+    grep -qx "alpha: https://api.foo.com foo-model" <<< "$output"
+check "switching projects drops keys the new profile lacks" \
+    grep -qx "beta: https://api.bar.com unset" <<< "$output"
+check "leaving restores the value exported before entering" \
+    grep -qx "outside: https://mine.example.com unset unset" <<< "$output"
+
+echo ""
+echo "=== Testing hooks for unsupported shells ==="
+output=$($CCM hook pwsh 2>&1) && code=0 || code=$?
+check "ccm hook pwsh fails" [ $code -ne 0 ]
+check "the failure lists the supported shells" grep -q "possible values: bash, zsh, fish" <<< "$output"
+check_not "ccm hook-env --shell dotenv fails" eval "$CCM hook-env --shell dotenv 2> /dev/null"
+
+finish "shell"
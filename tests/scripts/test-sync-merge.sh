@@ -0,0 +1,140 @@
+#!/bin/bash
+# Test script for three-way sync against the last-applied snapshot, and for
+# switching to profiles that extend others or merge into settings.json
+
+source "$(dirname "${BASH_SOURCE[0]}")/lib.sh" sync
+PROFILE="$CCM_CONFIG_DIR/profiles/bar.json"
+
+cat > "$PROFILE" << 'EOF'
+{
+  "env": {
+    "ANTHROPIC_BASE_URL": "https://api.bar.com",
+    "ANTHROPIC_MODEL": "model-a",
+    "API_TIMEOUT_MS": "1000"
+  }
+}
+EOF
+cat > "$CCM_CONFIG_DIR/profiles/foo.json" << 'EOF'
+{
+  "env": {
+    "ANTHROPIC_BASE_URL": "https://api.foo.com"
+  }
+}
+EOF
+
+echo ""
+echo "=== Test 1: Non-conflicting edits on both sides are merged by sync ==="
+$CCM switch bar < /dev/null
+sed -i 's/model-a/model-b/' "$CLAUDE_SETTINGS_PATH"
+sed -i 's/"1000"/"2000"/' "$PROFILE"
+$CCM sync < /dev/null
+check "Profile picked up the settings.json edit" grep -q "model-b" "$PROFILE"
+check "settings.json picked up the profile edit" grep -q '"2000"' "$CLAUDE_SETTINGS_PATH"
+
+echo ""
+echo "=== Test 2: Settings edits are kept without prompting on switch ==="
+sed -i 's/model-b/model-c/' "$CLAUDE_SETTINGS_PATH"
+$CCM switch foo < /dev/null
+check "Profile kept the settings.json edit" grep -q "model-c" "$PROFILE"
+check "Switched to 'foo'" grep -q "api.foo.com" "$CLAUDE_SETTINGS_PATH"
+
+echo ""
+echo "=== Test 3: Conflicts are prompted for ==="
+$CCM switch bar < /dev/null
+sed -i 's/model-c/from-settings/' "$CLAUDE_SETTINGS_PATH"
+sed -i 's/model-c/from-profile/' "$PROFILE"
+echo "c" | $CCM switch foo
+check "Cancel left settings.json alone" grep -q "from-settings" "$CLAUDE_SETTINGS_PATH"
+echo "s" | $CCM switch foo
+check "Choosing settings.json updated the profile" grep -q "from-settings" "$PROFILE"
+
+cat > "$CCM_CONFIG_DIR/profiles/base.json" << 'EOF'
+{
+  "env": {
+    "ANTHROPIC_BASE_URL": "https://api.base.com",
+    "ANTHROPIC_MODEL": "base-model",
+    "API_TIMEOUT_MS": "3000"
+  }
+}
+EOF
+cat > "$CCM_CONFIG_DIR/profiles/fast.json" << 'EOF'
+{
+  "env": {
+    "ANTHROPIC_SMALL_FAST_MODEL": "fast-model",
+    "API_TIMEOUT_MS": "4000"
+  }
+}
+EOF
+cat > "$CCM_CONFIG_DIR/profiles/child.json" << 'EOF'
+{
+  "extends": ["base", "fast"],
+  "env": {
+    "ANTHROPIC_MODEL": "child-model"
+  }
+}
+EOF
+cat > "$CCM_CONFIG_DIR/profiles/loop-a.json" << 'EOF'
+{ "extends": "loop-b" }
+EOF
+cat > "$CCM_CONFIG_DIR/profiles/loop-b.json" << 'EOF'
+{ "extends": "loop-a" }
+EOF
+
+echo ""
+echo "=== Test 4: Switching resolves extends ==="
+$CCM switch child < /dev/null
+check "Inherited values are written" [ "$(setting ANTHROPIC_BASE_URL)" = "https://api.base.com" ]
+check "Later parents override earlier ones" [ "$(setting API_TIMEOUT_MS)" = "4000" ]
+check "The profile's own values override its parents" [ "$(setting ANTHROPIC_MODEL)" = "child-model" ]
+check_not "extends is not written to settings.json" grep -q '"extends"' "$CLAUDE_SETTINGS_PATH"
+check "show --resolved prints the merged profile" \
+    grep -q "fast-model" <<< "$($CCM show child --resolved)"
+cp "$CLAUDE_SETTINGS_PATH" "$TEST_DIR/before-loop.json"
+output=$($CCM switch loop-a --no-validate < /dev/null 2>&1) && code=0 || code=$?
+check "Switching to a cycle fails" [ $code -ne 0 ]
+check "The cycle is named" grep -q "Profile inheritance cycle: loop-a -> loop-b -> loop-a" <<< "$output"
+check "settings.json is left alone" cmp -s "$CLAUDE_SETTINGS_PATH" "$TEST_DIR/before-loop.json"
+
+cat > "$CCM_CONFIG_DIR/profiles/merge-a.json" << 'EOF'
+{
+  "switchMode": "merge",
+  "env": {
+    "ANTHROPIC_BASE_URL": "https://api.merge-a.com",
+    "API_TIMEOUT_MS": "5000"
+  }
+}
+EOF
+cat > "$CCM_CONFIG_DIR/profiles/merge-b.json" << 'EOF'
+{
+  "switchMode": "merge",
+  "env": {
+    "ANTHROPIC_MODEL": "merge-b-model"
+  }
+}
+EOF
+
+echo ""
+echo "=== Test 5: Merge mode keeps the user's global settings ==="
+$CCM switch merge-a < /dev/null
+check "The profile's keys are merged in" [ "$(setting ANTHROPIC_BASE_URL)" = "https://api.merge-a.com" ]
+check_not "The keys of 'child' are dropped" grep -q "child-model" "$CLAUDE_SETTINGS_PATH"
+check_not "switchMode is not written to settings.json" grep -q '"switchMode"' "$CLAUDE_SETTINGS_PATH"
+python3 - "$CLAUDE_SETTINGS_PATH" << 'PY'
+import json, sys
+settings = json.load(open(sys.argv[1]))
+settings["permissions"] = {"allow": ["Bash(ls:*)"]}
+settings["hooks"] = {"Stop": [{"hooks": [{"type": "command", "command": "true"}]}]}
+json.dump(settings, open(sys.argv[1], "w"), indent=2)
+PY
+output=$($CCM switch merge-b < /dev/null 2>&1)
+check_not "Settings the profile does not own are not synced into it" grep -q "Kept" <<< "$output"
+check "permissions are kept" grep -q "Bash(ls:\*)" "$CLAUDE_SETTINGS_PATH"
+check "hooks are kept" grep -q '"Stop"' "$CLAUDE_SETTINGS_PATH"
+check "The new profile's keys are written" [ "$(setting ANTHROPIC_MODEL)" = "merge-b-model" ]
+check "The previous profile's keys are dropped" \
+    python3 -c "import json,sys; env = json.load(open(sys.argv[1]))['env']; sys.exit('ANTHROPIC_BASE_URL' in env or 'API_TIMEOUT_MS' in env)" \
+    "$CLAUDE_SETTINGS_PATH"
+$CCM switch merge-a --mode replace < /dev/null
+check_not "--mode replace drops them" grep -q '"Stop"' "$CLAUDE_SETTINGS_PATH"
+
+finish "sync"
@@ -0,0 +1,131 @@
+#!/bin/bash
+# Test script for `ccm validate` and the checks run by add, edit and switch
+
+source "$(dirname "${BASH_SOURCE[0]}")/lib.sh" validate
+
+cat > "$PROFILES/good.json" << 'EOF'
+{
+  "env": {
+    "ANTHROPIC_BASE_URL": "https://api.example.com",
+    "ANTHROPIC_AUTH_TOKEN": "sk-test",
+    "API_TIMEOUT_MS": "600000"
+  },
+  "permissions": { "allow": ["Bash(ls:*)"] }
+}
+EOF
+cat > "$PROFILES/leaky.json" << 'EOF'
+{
+  "env": {
+    "ANTHROPIC_BASE_URL": "https://user:hunter2@ api.example.com",
+    "ANTHROPIC_AUTH_TOKEN": ["sk-leaked-token"]
+  }
+}
+EOF
+cat > "$PROFILES/typo.json" << 'EOF'
+{
+  "env": {
+    "ANTHROPIC_BASE_URL": "https://api.example.com",
+    "ANTHROPIC_BASEURL": "https://api.example.com",
+    "ANTHROPIC_AUTH_TOKEN": "sk-test"
+  }
+}
+EOF
+cat > "$PROFILES/bad.json" << 'EOF'
+{
+  "extends": "missing",
+  "env": {
+    "ANTHROPIC_BASE_URL": "api.example.com",
+    "API_TIMEOUT_MS": "5min"
+  }
+}
+EOF
+
+expect() {
+    local status=$1 pattern=$2
+    shift 2
+    local cmd="${*//$CCM/ccm}" output code=0
+    output=$("$@" < /dev/null 2>&1) || code=$?
+    if { [ "$status" = ok ] && [ $code -ne 0 ]; } || { [ "$status" = fail ] && [ $code -eq 0 ]; }; then
+        echo "✗ $cmd: unexpected exit code $code"
+        echo "$output"
+        FAILED=1
+    elif ! grep -qF -- "$pattern" <<< "$output"; then
+        echo "✗ $cmd: output does not mention '$pattern'"
+        echo "$output"
+        FAILED=1
+    else
+        echo "✓ $cmd ($pattern)"
+    fi
+}
+
+echo ""
+echo "=== Testing ccm validate ==="
+expect ok "✓ good" "$CCM" validate good
+expect ok "did you mean ANTHROPIC_BASE_URL?" "$CCM" validate typo
+expect fail '$.env.API_TIMEOUT_MS: expected a non-negative integer, got "5min"' "$CCM" validate bad
+expect fail "\$.extends: profile 'missing' does not exist" "$CCM" validate bad
+expect fail "2 of 4 profile(s) have errors" "$CCM" validate
+expect fail '"valid": false' "$CCM" validate bad --output json
+expect fail 'URL "https://user:********@ api.example.com" contains whitespace' "$CCM" validate leaky
+expect fail "ANTHROPIC_AUTH_TOKEN: expected a string" "$CCM" validate leaky
+if "$CCM" validate leaky 2>&1 | grep -qE "hunter2|sk-leaked"; then
+    echo "✗ validate printed a credential"
+    FAILED=1
+else
+    echo "✓ validate never prints credentials"
+fi
+check "validate --output json prints only the reports" \
+    eval '"$CCM" validate bad --output json 2> /dev/null | python3 -m json.tool > /dev/null'
+
+cat > "$PROFILES/loop-a.json" << 'EOF'
+{ "extends": "loop-b", "env": { "ANTHROPIC_MODEL": "a" } }
+EOF
+cat > "$PROFILES/loop-b.json" << 'EOF'
+{ "extends": ["good", "loop-a"] }
+EOF
+expect fail "\$.extends: inheritance cycle: loop-b -> loop-a -> loop-b" "$CCM" validate loop-a
+expect fail "\$.extends[1]: inheritance cycle: loop-a -> loop-b -> loop-a" "$CCM" validate loop-b
+expect fail "inheritance cycle" "$CCM" switch loop-a
+rm "$PROFILES/loop-a.json" "$PROFILES/loop-b.json"
+
+echo ""
+echo "=== Testing checks on add, switch and edit ==="
+expect fail "expected an http:// or https:// URL" \
+    env TOKEN=sk-test "$CCM" add new --base-url api.example.com --token-env TOKEN --no-input
+[ ! -f "$PROFILES/new.json" ] || { echo "✗ invalid profile was saved"; FAILED=1; }
+expect ok "created successfully" \
+    env TOKEN=sk-test "$CCM" add new --base-url api.example.com --token-env TOKEN --no-input --no-validate
+expect ok "created successfully" env TOKEN=sk-test "$CCM" add prompted --token-env TOKEN
+grep -q '"https://api.anthropic.com"' "$PROFILES/prompted.json" \
+    || { echo "✗ an empty base URL answer did not use the default"; FAILED=1; }
+
+expect fail "use --no-validate" "$CCM" switch bad
+[ ! -f "$CLAUDE_SETTINGS_PATH" ] || { echo "✗ switch applied an invalid profile"; FAILED=1; }
+expect ok "Switched" "$CCM" switch typo
+
+cat > "$PROFILES/tolerated.json" << 'EOF'
+{
+  "env": {
+    "ANTHROPIC_BASE_URL": "https://api.example.com",
+    "ANTHROPIC_AUTH_TOKEN": "sk-test",
+    "CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC": "true"
+  }
+}
+EOF
+expect fail 'expected 1 or 0, got "true"' "$CCM" validate tolerated
+expect ok "Switched" "$CCM" switch tolerated --no-validate
+expect ok '"CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC": "true"' "$CCM" show tolerated --resolved
+expect ok "CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC='true'" "$CCM" env tolerated --shell bash
+expect ok '"base_url": "https://api.example.com"' "$CCM" ls --output json
+
+cat > "$TEST_DIR/editor.sh" << 'EOF'
+#!/bin/sh
+sed -i 's|https://api.example.com|api.example.com|' "$1"
+EOF
+chmod +x "$TEST_DIR/editor.sh"
+cp "$PROFILES/good.json" "$TEST_DIR/good.json"
+expect fail "were discarded" env EDITOR="$TEST_DIR/editor.sh" "$CCM" edit good
+cmp -s "$PROFILES/good.json" "$TEST_DIR/good.json" || { echo "✗ invalid edit was kept"; FAILED=1; }
+expect ok "edited successfully" env EDITOR="$TEST_DIR/editor.sh" "$CCM" edit good --no-validate
+
+finish "validation"
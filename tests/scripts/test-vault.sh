@@ -0,0 +1,73 @@
+#!/bin/bash
+# Test script for the secret vault and `ccm secret`
+
+source "$(dirname "${BASH_SOURCE[0]}")/lib.sh" vault
+
+export CCM_VAULT_PASSPHRASE="test-passphrase"
+VAULT="$CCM_CONFIG_DIR/vault.json"
+
+cat > "$PROFILES/kimi.json" << 'EOF'
+{
+  "env": {
+    "ANTHROPIC_BASE_URL": "https://api.kimi.com",
+    "ANTHROPIC_AUTH_TOKEN": "sk-kimi-plaintext"
+  }
+}
+EOF
+
+echo ""
+echo "=== Testing ccm secret ==="
+echo "sk-deepseek" | $CCM secret set deepseek --stdin > /dev/null
+check "vault file created" [ -f "$VAULT" ]
+check "vault is readable by the owner only" [ "$(stat -c %a "$VAULT")" = 600 ]
+check "vault does not contain the secret in plaintext" [ -z "$(grep sk-deepseek "$VAULT")" ]
+check "no temporary file left behind" [ -z "$(ls "$CCM_CONFIG_DIR" | grep tmp)" ]
+check "get prints the secret" [ "$($CCM secret get deepseek)" = sk-deepseek ]
+check "ls lists the name" grep -q deepseek <<< "$($CCM secret ls)"
+check "ls never prints values" [ -z "$($CCM secret ls | grep sk-deepseek)" ]
+
+chmod 644 "$VAULT"
+echo "sk-other" | $CCM secret set other --stdin > /dev/null
+check "saving restores owner-only permissions" [ "$(stat -c %a "$VAULT")" = 600 ]
+
+output=$(CCM_VAULT_PASSPHRASE=wrong $CCM secret get deepseek 2>&1) && code=0 || code=$?
+check "wrong passphrase fails" [ $code -ne 0 ]
+check "wrong passphrase is reported" grep -q "wrong passphrase" <<< "$output"
+
+$CCM secret rm other > /dev/null
+check "rm removes the secret" [ -z "$($CCM secret ls | grep other)" ]
+
+echo ""
+echo "=== Testing vault references ==="
+cat > "$PROFILES/glm.json" << 'EOF'
+{
+  "env": {
+    "ANTHROPIC_BASE_URL": "https://api.glm.com",
+    "ANTHROPIC_AUTH_TOKEN": "${GLM_TOKEN}"
+  }
+}
+EOF
+$CCM secret migrate > /dev/null
+check "migrate replaces the token with a reference" grep -q '"vault:kimi"' "$PROFILES/kimi.json"
+check "migrate leaves placeholders alone" grep -q '"${GLM_TOKEN}"' "$PROFILES/glm.json"
+check_not "migrate does not store placeholders" grep -q '"glm"' <<< "$($CCM secret ls --output json)"
+GLM_TOKEN=sk-glm-env $CCM switch glm < /dev/null > /dev/null
+check "switch expands the kept placeholder" [ "$(setting ANTHROPIC_AUTH_TOKEN)" = sk-glm-env ]
+check "migrate removes the plaintext token" [ -z "$(grep sk-kimi-plaintext "$PROFILES/kimi.json")" ]
+$CCM switch kimi < /dev/null > /dev/null
+check "switch resolves the reference" [ "$(setting ANTHROPIC_AUTH_TOKEN)" = sk-kimi-plaintext ]
+
+echo ""
+echo "=== Testing --output json ==="
+is_json() {
+    python3 -c "import json,sys; json.load(sys.stdin)"
+}
+check "secret ls prints only json" is_json <<< "$($CCM secret ls --output json)"
+check "secret ls json lists the name" grep -q '"deepseek"' <<< "$($CCM secret ls --output json)"
+check "switch prints only json" is_json <<< "$($CCM switch kimi --output json < /dev/null 2> /dev/null)"
+check "switch reports the change" grep -q '"changed": true' <<< "$($CCM switch kimi --output json < /dev/null 2> /dev/null)"
+check "env json holds the resolved token" grep -q sk-kimi-plaintext <<< "$($CCM env kimi --output json)"
+output=$($CCM hook-env --shell bash --output json 2>&1) && code=0 || code=$?
+check "hook-env rejects --output" [ $code -ne 0 ]
+
+finish "vault"